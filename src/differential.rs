@@ -1,21 +1,242 @@
+use crate::{EvalError, Expr, Span};
 
+// Central finite difference f'(x) ~ (f(x + h) - f(x - h)) / 2h. The step scales
+// with |x| and uses cbrt(machine epsilon), which balances truncation error
+// against rounding error for this formula.
+pub fn derivative<F>(mut f: F, x: f64) -> Result<f64, EvalError>
+where
+    F: FnMut(f64) -> Result<f64, EvalError>,
+{
+    let h = f64::EPSILON.cbrt() * x.abs().max(1.0);
+    Ok((f(x + h)? - f(x - h)?) / (2.0 * h))
+}
+
+// Partial derivative of f at `point` along coordinate `index`, holding the
+// other coordinates fixed.
+pub fn partial<F>(mut f: F, point: &[f64], index: usize) -> Result<f64, EvalError>
+where
+    F: FnMut(&[f64]) -> Result<f64, EvalError>,
+{
+    let mut at = point.to_vec();
+    derivative(
+        |t| {
+            at[index] = t;
+            f(&at)
+        },
+        point[index],
+    )
+}
+
+pub fn gradient<F>(mut f: F, point: &[f64]) -> Result<Vec<f64>, EvalError>
+where
+    F: FnMut(&[f64]) -> Result<f64, EvalError>,
+{
+    (0..point.len()).map(|index| partial(&mut f, point, index)).collect()
+}
+
+fn num(n: f64) -> Expr {
+    Expr::Number(n)
+}
+
+fn bin(left: Expr, op: &str, right: Expr) -> Expr {
+    Expr::BinaryOp { left: Box::new(left), op: op.to_string(), right: Box::new(right) }
+}
+
+fn call(name: &str, arg: Expr) -> Expr {
+    Expr::FunctionCall { name: name.to_string(), args: vec![arg], span: Span::default() }
+}
 
-fn diff_add(a: f64, b: f64) -> f64 {
-    return a + b;
+fn neg(expr: Expr) -> Expr {
+    Expr::UnaryOp { op: "-".to_string(), expr: Box::new(expr) }
 }
 
-fn diff_sub(a: f64, b: f64) -> f64 {
-    return a - b;
+fn depends_on(expr: &Expr, var: &str) -> bool {
+    match expr {
+        Expr::Number(_) => false,
+        Expr::Variable(name, _) => name == var,
+        Expr::UnaryOp { expr, .. } => depends_on(expr, var),
+        Expr::BinaryOp { left, right, .. } => depends_on(left, var) || depends_on(right, var),
+        Expr::FunctionCall { args, .. } => args.iter().any(|a| depends_on(a, var)),
+        _ => true,
+    }
 }
 
-fn diff_mul(a: f64, b: f64) -> f64 {
-    return a * b;
+// Symbolic derivative of `expr` with respect to `var`, using the sum, product,
+// quotient and chain rules. The result is correct but unsimplified.
+pub fn differentiate(expr: &Expr, var: &str) -> Result<Expr, EvalError> {
+    match expr {
+        Expr::Number(_) => Ok(num(0.0)),
+        Expr::Variable(name, _) => Ok(num(if name == var { 1.0 } else { 0.0 })),
+        Expr::UnaryOp { op, expr } => match op.as_str() {
+            "+" => differentiate(expr, var),
+            "-" => Ok(neg(differentiate(expr, var)?)),
+            _ => Err(format!("Cannot differentiate unary operator: {}", op).into()),
+        },
+        Expr::BinaryOp { left, op, right } => {
+            let (u, v) = (left.as_ref().clone(), right.as_ref().clone());
+            match op.as_str() {
+                "+" | "-" => Ok(bin(differentiate(left, var)?, op, differentiate(right, var)?)),
+                "*" => Ok(bin(
+                    bin(differentiate(left, var)?, "*", v.clone()),
+                    "+",
+                    bin(u, "*", differentiate(right, var)?),
+                )),
+                "/" => Ok(bin(
+                    bin(
+                        bin(differentiate(left, var)?, "*", v.clone()),
+                        "-",
+                        bin(u, "*", differentiate(right, var)?),
+                    ),
+                    "/",
+                    bin(v, "^", num(2.0)),
+                )),
+                // power rule: d(u^n) = n * u^(n-1) * u'
+                "^" if !depends_on(right, var) => Ok(bin(
+                    bin(v.clone(), "*", bin(u, "^", bin(v, "-", num(1.0)))),
+                    "*",
+                    differentiate(left, var)?,
+                )),
+                // exponential: d(a^v) = a^v * ln(a) * v'
+                "^" if !depends_on(left, var) => Ok(bin(
+                    bin(expr.clone(), "*", call("ln", u)),
+                    "*",
+                    differentiate(right, var)?,
+                )),
+                // general case: d(u^v) = u^v * (v' * ln(u) + v * u' / u)
+                "^" => Ok(bin(
+                    expr.clone(),
+                    "*",
+                    bin(
+                        bin(differentiate(right, var)?, "*", call("ln", u.clone())),
+                        "+",
+                        bin(bin(v, "*", differentiate(left, var)?), "/", u),
+                    ),
+                )),
+                _ => Err(format!("Cannot differentiate operator: {}", op).into()),
+            }
+        }
+        Expr::FunctionCall { name, args, .. } => {
+            let [u] = args.as_slice() else {
+                return Err(format!("Cannot differentiate function '{}'", name).into());
+            };
+            let du = differentiate(u, var)?;
+            let outer = match name.as_str() {
+                "sin" => call("cos", u.clone()),
+                "cos" => neg(call("sin", u.clone())),
+                "tan" => bin(num(1.0), "/", bin(call("cos", u.clone()), "^", num(2.0))),
+                "exp" => call("exp", u.clone()),
+                "ln" => bin(num(1.0), "/", u.clone()),
+                "sqrt" => bin(num(1.0), "/", bin(num(2.0), "*", call("sqrt", u.clone()))),
+                _ => return Err(format!("Cannot differentiate function '{}'", name).into()),
+            };
+            Ok(bin(outer, "*", du))
+        }
+        _ => Err("Cannot differentiate this expression".into()),
+    }
 }
 
-fn diff_div(a: f64, b: f64) -> f64 {
-    return a / b;
+// Definite integral of f over [a, b] by adaptive Simpson's rule. Reversed
+// bounds negate the result.
+pub fn integrate<F>(mut f: F, a: f64, b: f64, tolerance: f64) -> Result<f64, EvalError>
+where
+    F: FnMut(f64) -> Result<f64, EvalError>,
+{
+    if a > b {
+        return Ok(-integrate(f, b, a, tolerance)?);
+    }
+    let (fa, fb, fm) = (f(a)?, f(b)?, f((a + b) / 2.0)?);
+    let whole = simpson(a, b, fa, fm, fb);
+    adaptive_simpson(&mut f, a, b, fa, fm, fb, whole, tolerance, 50)
 }
 
-fn diff_exp(a: f64, b: f64) -> f64 {
-    return a.powf(b);
-}
\ No newline at end of file
+fn simpson(a: f64, b: f64, fa: f64, fm: f64, fb: f64) -> f64 {
+    (b - a) / 6.0 * (fa + 4.0 * fm + fb)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn adaptive_simpson<F>(f: &mut F, a: f64, b: f64, fa: f64, fm: f64, fb: f64, whole: f64, tolerance: f64, depth: u32) -> Result<f64, EvalError>
+where
+    F: FnMut(f64) -> Result<f64, EvalError>,
+{
+    let m = (a + b) / 2.0;
+    let (lm, rm) = ((a + m) / 2.0, (m + b) / 2.0);
+    let (flm, frm) = (f(lm)?, f(rm)?);
+    let left = simpson(a, m, fa, flm, fm);
+    let right = simpson(m, b, fm, frm, fb);
+    let delta = left + right - whole;
+    if depth == 0 || delta.abs() <= 15.0 * tolerance {
+        // Richardson extrapolation of the two estimates
+        return Ok(left + right + delta / 15.0);
+    }
+    Ok(adaptive_simpson(f, a, m, fa, flm, fm, left, tolerance / 2.0, depth - 1)?
+        + adaptive_simpson(f, m, b, fm, frm, fb, right, tolerance / 2.0, depth - 1)?)
+}
+
+// Finds a root of f by Newton's method starting from `guess`, using the
+// numeric derivative for each step.
+pub fn solve<F>(mut f: F, guess: f64, max_iterations: usize) -> Result<f64, EvalError>
+where
+    F: FnMut(f64) -> Result<f64, EvalError>,
+{
+    let mut x = guess;
+    for _ in 0..max_iterations {
+        let fx = f(x)?;
+        if fx == 0.0 {
+            return Ok(x);
+        }
+        let slope = derivative(&mut f, x)?;
+        if slope == 0.0 || !slope.is_finite() {
+            return Err(format!("solve: zero or undefined derivative at {}", x).into());
+        }
+        let next = x - fx / slope;
+        if (next - x).abs() <= 1e-12 * x.abs().max(1.0) {
+            return Ok(next);
+        }
+        x = next;
+    }
+    Err("did not converge".into())
+}
+
+// Limit of f at x0, from f sampled on both sides at x0 ± 10^-k for shrinking
+// steps. The two sides must approach each other, and their average, with
+// Richardson extrapolation removing the leading error term, must settle. The
+// estimate is taken where successive values agree best, before cancellation
+// error from the smallest steps sets in.
+pub fn limit<F>(mut f: F, x0: f64, tolerance: f64) -> Result<f64, EvalError>
+where
+    F: FnMut(f64) -> Result<f64, EvalError>,
+{
+    let mut samples = Vec::new();
+    for k in 1..=LIMIT_STEPS {
+        let h = 10f64.powi(-k);
+        samples.push((f(x0 - h)?, f(x0 + h)?));
+    }
+    // the average of both sides has only even powers of h in its error
+    let average: Vec<f64> = samples.iter().map(|(left, right)| (left + right) / 2.0).collect();
+    let extrapolated: Vec<f64> = average.windows(2).map(|pair| (100.0 * pair[1] - pair[0]) / 99.0).collect();
+    let best = (1..extrapolated.len())
+        .map(|k| ((extrapolated[k] - extrapolated[k - 1]).abs(), k))
+        .min_by(|a, b| a.0.total_cmp(&b.0));
+    let close = |a: f64, b: f64| (a - b).abs() <= tolerance * a.abs().max(b.abs()).max(1.0);
+    let (left, right) = samples[samples.len() - 1];
+    match best {
+        Some((_, k)) if close(extrapolated[k], extrapolated[k - 1]) && close(left, right) => {
+            Ok(round_significant(extrapolated[k], LIMIT_DIGITS))
+        }
+        _ => Err("limit does not exist".into()),
+    }
+}
+
+// Steps 10^-1 down to 10^-LIMIT_STEPS are tried on each side.
+const LIMIT_STEPS: i32 = 6;
+// Significant digits a limit estimate is reported to, hiding the error of the
+// finite step.
+const LIMIT_DIGITS: i32 = 10;
+
+fn round_significant(x: f64, digits: i32) -> f64 {
+    if x == 0.0 || !x.is_finite() {
+        return x;
+    }
+    let scale = 10f64.powi(digits - x.abs().log10().ceil() as i32);
+    (x * scale).round() / scale
+}
@@ -1,9 +1,71 @@
-fn dsin(x: f64) -> f64 {
-    return x.sin();
+use crate::{Expr, real_op};
+
+// Degree-mode trig: arguments (or, for the inverses, results) are in degrees.
+
+pub fn dsin(x: f64) -> f64 {
+    x.to_radians().sin()
 }
-fn dcos(x: f64) -> f64 {
-    return x.cos();
+pub fn dcos(x: f64) -> f64 {
+    x.to_radians().cos()
+}
+pub fn dtan(x: f64) -> f64 {
+    x.to_radians().tan()
+}
+
+pub fn dasin(x: f64) -> f64 {
+    x.asin().to_degrees()
+}
+pub fn dacos(x: f64) -> f64 {
+    x.acos().to_degrees()
+}
+pub fn datan(x: f64) -> f64 {
+    x.atan().to_degrees()
+}
+pub fn datan2(y: f64, x: f64) -> f64 {
+    y.atan2(x).to_degrees()
+}
+
+// Algebraic clean-up of a symbolic result such as a derivative: drops
+// additive zeros and factors of one, collapses products with zero and powers
+// of zero or one, folds numeric subexpressions, and gathers the constant
+// factors of a product in front. Simplifying twice gives the same result as
+// simplifying once.
+pub fn simplify(expr: &Expr) -> Expr {
+    match expr {
+        Expr::BinaryOp { left, op, right } => simplify_binary(simplify(left), op, simplify(right)),
+        Expr::UnaryOp { op, expr } => match (op.as_str(), simplify(expr)) {
+            ("+", expr) => expr,
+            ("-", Expr::Number(n)) => Expr::Number(-n),
+            ("-", Expr::UnaryOp { op, expr }) if op == "-" => *expr,
+            (_, expr) => Expr::UnaryOp { op: op.clone(), expr: Box::new(expr) },
+        },
+        Expr::FunctionCall { name, args, span } => Expr::FunctionCall { name: name.clone(), args: args.iter().map(simplify).collect(), span: *span },
+        _ => expr.clone(),
+    }
+}
+
+fn simplify_binary(left: Expr, op: &str, right: Expr) -> Expr {
+    use Expr::Number;
+    let is = |expr: &Expr, n: f64| matches!(expr, Number(x) if *x == n);
+    match (left, op, right) {
+        (Number(a), _, Number(b)) if let Ok(value) = real_op(a, op, b) && value.is_finite() && !(op == "/" && b == 0.0) => {
+            Number(value)
+        }
+        (zero, "+", x) | (x, "+" | "-", zero) if is(&zero, 0.0) => x,
+        (zero, "-", x) if is(&zero, 0.0) => Expr::UnaryOp { op: "-".to_string(), expr: Box::new(x) },
+        (zero, "*", _) | (_, "*", zero) if is(&zero, 0.0) => Number(0.0),
+        (zero, "/", _) if is(&zero, 0.0) => Number(0.0),
+        (one, "*", x) | (x, "*" | "/" | "^", one) if is(&one, 1.0) => x,
+        (_, "^", zero) if is(&zero, 0.0) => Number(1.0),
+        (one, "^", _) if is(&one, 1.0) => Number(1.0),
+        // constant factors go in front and are multiplied together
+        (x, "*", Number(b)) if !matches!(x, Number(_)) => simplify_binary(Number(b), "*", x),
+        (Number(a), "*", Expr::BinaryOp { left, op, right }) if op == "*" && let Number(b) = *left => {
+            simplify_binary(Number(a * b), "*", *right)
+        }
+        (Expr::BinaryOp { left, op: inner, right }, "*", x) if inner == "*" && matches!(*left, Number(_)) => {
+            simplify_binary(*left, "*", simplify_binary(*right, "*", x))
+        }
+        (left, op, right) => Expr::BinaryOp { left: Box::new(left), op: op.to_string(), right: Box::new(right) },
+    }
 }
-fn dtan(x: f64) -> f64 {
-    return x.tan();
-}
\ No newline at end of file
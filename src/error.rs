@@ -0,0 +1,65 @@
+use std::fmt;
+
+use crate::Span;
+
+// Why parsing or evaluation failed. Embedders can match on the kind; Display
+// gives the message shown to users.
+#[derive(Debug, Clone, PartialEq)]
+pub enum EvalError {
+    // the name, the closest known name if one is near enough, and where the
+    // name was used
+    UndefinedVariable { name: String, suggestion: Option<String>, span: Option<Span> },
+    UnknownFunction { name: String, suggestion: Option<String>, span: Option<Span> },
+    ArityMismatch { name: String, expected: usize, got: usize },
+    // an argument outside the set a function is defined on
+    DomainError { func: String, message: String },
+    DivByZero,
+    // assert() with a false condition, and the message it was given
+    AssertionFailed(Option<String>),
+    ParseError(String),
+    // anything else, described by its message
+    Runtime(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hint = |suggestion: &Option<String>| match suggestion {
+            Some(candidate) => format!(" (did you mean '{}'?)", candidate),
+            None => String::new(),
+        };
+        let position = |span: &Option<Span>| match span {
+            Some(span) => format!(" at line {}, column {}", span.line, span.column),
+            None => String::new(),
+        };
+        match self {
+            EvalError::UndefinedVariable { name, suggestion, span } => {
+                write!(f, "Undefined variable: {}{}{}", name, position(span), hint(suggestion))
+            }
+            EvalError::UnknownFunction { name, suggestion, span } => {
+                write!(f, "Unknown function: {}{}{}", name, position(span), hint(suggestion))
+            }
+            EvalError::ArityMismatch { name, expected, got } => {
+                write!(f, "Function '{}' expects {} argument(s), got {}", name, expected, got)
+            }
+            EvalError::DomainError { message, .. } => write!(f, "{}", message),
+            EvalError::DivByZero => write!(f, "division by zero"),
+            EvalError::AssertionFailed(Some(message)) => write!(f, "Assertion failed: {}", message),
+            EvalError::AssertionFailed(None) => write!(f, "Assertion failed"),
+            EvalError::ParseError(message) | EvalError::Runtime(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+impl From<String> for EvalError {
+    fn from(message: String) -> Self {
+        EvalError::Runtime(message)
+    }
+}
+
+impl From<&str> for EvalError {
+    fn from(message: &str) -> Self {
+        EvalError::Runtime(message.to_string())
+    }
+}
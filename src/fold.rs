@@ -0,0 +1,50 @@
+use crate::{Expr, factorial, real_op};
+
+// Replaces subexpressions whose operands are all literal numbers with their
+// value, so `(2 + 3) * x` becomes `5 * x`. Folding is done in floating point
+// and only where the result is an ordinary finite number: `1/0`, `(-8)^(1/3)`
+// and the like are left for evaluation to report or handle. Function calls
+// aren't folded, since their results can depend on settings such as the angle
+// mode.
+pub fn fold_constants(expr: Expr) -> Expr {
+    let fold = |expr: Box<Expr>| Box::new(fold_constants(*expr));
+    let fold_all = |exprs: Vec<Expr>| exprs.into_iter().map(fold_constants).collect();
+    match expr {
+        Expr::UnaryOp { op, expr } => match (op.as_str(), *fold(expr)) {
+            ("-", Expr::Number(n)) => Expr::Number(-n),
+            ("+", Expr::Number(n)) => Expr::Number(n),
+            ("!", Expr::Number(n)) if let Ok(value) = factorial(n) => Expr::Number(value),
+            (_, expr) => Expr::UnaryOp { op, expr: Box::new(expr) },
+        },
+        Expr::BinaryOp { left, op, right } => {
+            let (left, right) = (fold(left), fold(right));
+            match (&*left, &*right) {
+                // real_op doesn't stop division by zero
+                (Expr::Number(_), Expr::Number(r)) if op == "/" && *r == 0.0 => Expr::BinaryOp { left, op, right },
+                (Expr::Number(l), Expr::Number(r)) => match real_op(*l, &op, *r) {
+                    Ok(value) if value.is_finite() => Expr::Number(value),
+                    _ => Expr::BinaryOp { left, op, right },
+                },
+                _ => Expr::BinaryOp { left, op, right },
+            }
+        }
+        // a constant condition picks its branch
+        Expr::Conditional { cond, then, otherwise } => match *fold(cond) {
+            Expr::Number(n) if n.is_finite() => fold_constants(if n != 0.0 { *then } else { *otherwise }),
+            cond => Expr::Conditional { cond: Box::new(cond), then: fold(then), otherwise: fold(otherwise) },
+        },
+        Expr::Let { name, value, body } => Expr::Let { name, value: fold(value), body: fold(body) },
+        Expr::List(items) => Expr::List(fold_all(items)),
+        Expr::Lambda { params, body } => Expr::Lambda { params, body: fold(body) },
+        Expr::Piecewise(pieces) => {
+            Expr::Piecewise(pieces.into_iter().map(|(guard, value)| (fold_constants(guard), fold_constants(value))).collect())
+        }
+        Expr::Index { target, index } => Expr::Index { target: fold(target), index: fold(index) },
+        Expr::Slice { target, start, end } => Expr::Slice { target: fold(target), start: start.map(fold), end: end.map(fold) },
+        Expr::FunctionCall { name, args, span } => Expr::FunctionCall { name, args: fold_all(args), span },
+        Expr::Assignment { name, value } => Expr::Assignment { name, value: fold(value) },
+        Expr::FunctionDef { name, params, body } => Expr::FunctionDef { name, params, body: fold(body) },
+        Expr::Print(exprs) => Expr::Print(fold_all(exprs)),
+        Expr::Number(_) | Expr::Str(_) | Expr::Variable(..) | Expr::Unit(_) | Expr::Delete(_) => expr,
+    }
+}
@@ -0,0 +1,2037 @@
+mod differential;
+mod dmath;
+mod error;
+mod fold;
+mod matrix;
+pub mod pretty;
+mod units;
+pub mod value;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use pest::Parser;
+use pest::iterators::Pair;
+use pest_derive::Parser;
+
+pub use dmath::simplify;
+pub use error::EvalError;
+pub use fold::fold_constants;
+pub use pretty::{ast_tree, expr_to_string};
+pub use value::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AngleMode {
+    Radians,
+    Degrees,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumberFormat {
+    Plain,
+    Scientific,
+}
+
+// A host function callable from expressions, registered with State::register.
+pub type NativeFn = Box<dyn Fn(&[f64]) -> Result<f64, String>>;
+
+pub struct State {
+    pub vars: HashMap<String, Value>,
+    pub funcs: HashMap<String, Rc<(Vec<String>, Expr)>>,
+    pub funcs_native: HashMap<String, NativeFn>,
+    pub angle_mode: AngleMode,
+    // one frame of parameter bindings per active user function call
+    scopes: Vec<HashMap<String, Value>>,
+    // deepest nesting of user function calls. A self-call in tail position
+    // doesn't nest, but counts too: a function may make TAIL_CALLS_PER_FRAME
+    // times this many of them in a row, so that `f(x) = f(x)` still fails
+    // promptly.
+    pub max_call_depth: usize,
+    // bytes of stack evaluation may use before failing as too deep. This is
+    // measured as the distance from the outermost eval's frame, so it counts
+    // only what evaluation itself uses: it must be smaller than the stack the
+    // calling thread has left, or deep input still overflows. The default
+    // fits a thread with at least 2 MiB (the size Rust gives spawned
+    // threads); embedders on smaller threads must lower it, and main.rs
+    // raises it for its larger interpreter thread.
+    pub max_stack_bytes: usize,
+    pub max_loop_iterations: usize,
+    // digits after the decimal point when printing; None prints full precision
+    pub precision: Option<usize>,
+    pub number_format: NumberFormat,
+    // evaluate literals as fractions so that 1/3 + 1/6 gives 1/2
+    pub exact: bool,
+    // evaluate literals as decimals so that 0.1 + 0.2 gives exactly 0.3
+    pub decimal: bool,
+    // let x/0 give inf or nan as in IEEE 754 instead of an error
+    pub ieee_division: bool,
+    // fail as soon as any intermediate result is NaN or infinite
+    pub strict_finite: bool,
+    // print each reduction step, e.g. `3 * 4 = 12`, as it is evaluated
+    pub trace: bool,
+    // how many traced steps enclose the one being evaluated
+    trace_depth: usize,
+    // reuse results of pure user functions called again with the same arguments
+    pub memoize: bool,
+    // cached results by call, and which functions are known to be pure. Both
+    // are cleared whenever a function is defined or deleted or a global is
+    // assigned.
+    memo: HashMap<String, Value>,
+    pure_funcs: HashMap<String, bool>,
+    // address of the outermost eval's frame while evaluating
+    stack_base: Option<usize>,
+    // xorshift state for rand(); never zero
+    rng: u64,
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 1000;
+pub const TAIL_CALLS_PER_FRAME: usize = 100;
+pub const DEFAULT_MAX_STACK_BYTES: usize = 1024 * 1024;
+// Most digits after the decimal point that can be asked for; Rust's
+// formatting can't go past u16::MAX.
+pub const MAX_PRECISION: usize = 1000;
+pub const DEFAULT_MAX_LOOP_ITERATIONS: usize = 1_000_000;
+const INTEGRATION_TOLERANCE: f64 = 1e-10;
+const SOLVE_MAX_ITERATIONS: usize = 100;
+const LIMIT_TOLERANCE: f64 = 1e-4;
+
+// Names understood without a definition, used for "did you mean" suggestions.
+// Built-in functions can also be used as values (`g = sin`); special forms
+// can't, since they need their arguments unevaluated.
+const BUILTIN_FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "asin", "acos", "atan", "atan2", "sinh", "cosh", "tanh", "asinh", "acosh", "atanh",
+    "ln", "log10", "log2", "log", "exp", "sqrt", "cbrt", "floor", "ceil", "round", "trunc", "sign", "abs",
+    "max", "min", "gcd", "lcm", "fact", "gamma", "ncr", "npr", "clamp", "lerp", "hypot", "mod", "not",
+    "deg2rad", "rad2deg", "band", "bor", "bxor", "shl", "shr", "re", "im", "conj", "arg", "sum", "mean",
+    "stddev", "range", "transpose", "det", "inv", "dot", "cross", "norm", "rand", "normpdf", "normcdf", "erf", "erfc", "hex", "bin", "oct", "format", "assert",
+];
+// built-ins whose result isn't determined by their arguments
+const IMPURE_BUILTINS: &[&str] = &["rand"];
+const SPECIAL_FORMS: &[&str] = &[
+    "if", "and", "or", "while", "diff", "integrate", "solve", "prod", "taylor", "deriv", "map", "filter", "reduce",
+    "defined", "limit", "partial", "gradient",
+];
+const CONSTANTS: &[&str] = &["pi", "e", "tau", "phi", "inf", "nan", "i"];
+// Furthest edit distance at which a known name is still suggested.
+const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+impl State {
+    pub fn new() -> Self {
+        let mut state = State {
+            vars: HashMap::new(),
+            funcs: HashMap::new(),
+            funcs_native: HashMap::new(),
+            angle_mode: AngleMode::Radians,
+            scopes: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_stack_bytes: DEFAULT_MAX_STACK_BYTES,
+            max_loop_iterations: DEFAULT_MAX_LOOP_ITERATIONS,
+            precision: None,
+            number_format: NumberFormat::Plain,
+            exact: false,
+            decimal: false,
+            ieee_division: false,
+            strict_finite: false,
+            trace: false,
+            trace_depth: 0,
+            memoize: true,
+            memo: HashMap::new(),
+            pure_funcs: HashMap::new(),
+            stack_base: None,
+            rng: 0,
+        };
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |t| t.as_nanos() as u64);
+        state.seed(nanos);
+        state
+    }
+
+    pub fn format_value(&self, value: &Value) -> String {
+        match value {
+            Value::Real(x) => self.format_real(*x),
+            Value::Rational(num, 1) => num.to_string(),
+            Value::Rational(num, den) => format!("{}/{}", num, den),
+            Value::Complex(re, im) => value::format_complex(*re, *im, |x| self.format_real(x)),
+            Value::List(items) => value::format_list(items, |item| self.format_value(item)),
+            Value::Matrix(rows) => matrix::format_matrix(rows, |x| self.format_real(x)),
+            Value::Quantity(x, dims) => format!("{} {}", self.format_real(*x), units::format_dims(dims)),
+            Value::Decimal(..) | Value::Str(_) | Value::Lambda(_) | Value::Function(_) => value.to_string(),
+        }
+    }
+
+    fn format_real(&self, value: f64) -> String {
+        match (self.number_format, self.precision) {
+            (NumberFormat::Plain, None) => format!("{}", value),
+            (NumberFormat::Plain, Some(p)) => format!("{:.*}", p, value),
+            (NumberFormat::Scientific, None) => format!("{:e}", value),
+            (NumberFormat::Scientific, Some(p)) => format!("{:.*e}", p, value),
+        }
+    }
+
+    pub fn set_var(&mut self, name: &str, value: impl Into<Value>) {
+        self.vars.insert(name.to_string(), value.into());
+        self.clear_memo();
+    }
+
+    pub fn get_var(&self, name: &str) -> Option<Value> {
+        self.vars.get(name).cloned()
+    }
+
+    // Variables and user functions as statements that recreate them, one per
+    // line. Native functions and settings are not included.
+    pub fn to_script(&self) -> String {
+        let mut vars: Vec<_> = self.vars.iter().collect();
+        vars.sort_by(|a, b| a.0.cmp(b.0));
+        let mut funcs: Vec<_> = self.funcs.iter().collect();
+        funcs.sort_by(|a, b| a.0.cmp(b.0));
+        let vars = vars.into_iter().map(|(name, value)| format!("{} = {}\n", name, pretty::value_to_source(value)));
+        let funcs = funcs.into_iter().map(|(name, func)| {
+            let (params, body) = &**func;
+            let def = Expr::FunctionDef { name: name.clone(), params: params.clone(), body: Box::new(body.clone()) };
+            format!("{}\n", pretty::expr_to_string(&def))
+        });
+        vars.chain(funcs).collect()
+    }
+
+    // Registers a Rust closure as a function taking exactly `arity` arguments.
+    // It takes precedence over a built-in of the same name.
+    pub fn register<F>(&mut self, name: &str, arity: usize, func: F)
+    where
+        F: Fn(&[f64]) -> Result<f64, String> + 'static,
+    {
+        let fname = name.to_string();
+        let checked = move |args: &[f64]| {
+            if args.len() != arity {
+                return Err(format!("Function '{}' expects {} argument(s), got {}", fname, arity, args.len()));
+            }
+            func(args)
+        };
+        self.funcs_native.insert(name.to_string(), Box::new(checked));
+    }
+
+    fn lookup(&self, name: &str) -> Option<Value> {
+        match self.scopes.last() {
+            Some(frame) if frame.contains_key(name) => frame.get(name).cloned(),
+            _ => self.vars.get(name).cloned(),
+        }
+    }
+
+    // The lambda or function reference stored in variable `name`, if any.
+    fn lookup_callable(&self, name: &str) -> Option<Value> {
+        let frame = self.scopes.last().filter(|frame| frame.contains_key(name));
+        match frame.unwrap_or(&self.vars).get(name) {
+            Some(value @ (Value::Lambda(_) | Value::Function(_))) => Some(value.clone()),
+            _ => None,
+        }
+    }
+
+    fn is_function(&self, name: &str) -> bool {
+        self.funcs.contains_key(name) || self.funcs_native.contains_key(name) || BUILTIN_FUNCTIONS.contains(&name)
+    }
+
+    fn assign(&mut self, name: String, value: Value) {
+        match self.scopes.last_mut() {
+            Some(frame) => frame.insert(name, value),
+            None => {
+                self.clear_memo();
+                self.vars.insert(name, value)
+            }
+        };
+    }
+
+    fn define(&mut self, name: String, params: Vec<String>, body: Expr) {
+        self.funcs.insert(name, Rc::new((params, body)));
+        self.clear_memo();
+    }
+
+    fn clear_memo(&mut self) {
+        self.memo.clear();
+        self.pure_funcs.clear();
+    }
+
+    // Restarts rand() so that the same seed gives the same sequence. A new
+    // state is seeded from the clock.
+    pub fn seed(&mut self, seed: u64) {
+        // splitmix64 spreads nearby seeds apart
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        self.rng = (z ^ (z >> 31)).max(1);
+    }
+
+    // xorshift64*, scaled to [0, 1) from the top 53 bits
+    fn next_random(&mut self) -> f64 {
+        let mut x = self.rng;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.rng = x;
+        (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State::new()
+    }
+}
+
+#[derive(Parser)]
+#[grammar = "math.pest"]
+struct MathParser;
+
+// Where a node came from in the source, for pointing errors at it. Nodes
+// built by the interpreter itself have a default span with line 0.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    fn of(pair: &Pair<Rule>) -> Span {
+        let (line, column) = pair.line_col();
+        Span { start: pair.as_span().start(), end: pair.as_span().end(), line, column }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Number(f64),
+    Str(String),
+    Variable(String, Span),
+    // a unit after a number literal, e.g. the `cm` in `50 cm`
+    Unit(String),
+    UnaryOp { op: String, expr: Box<Expr> },
+    BinaryOp { left: Box<Expr>, op: String, right: Box<Expr> },
+    // cond ? then : otherwise
+    Conditional { cond: Box<Expr>, then: Box<Expr>, otherwise: Box<Expr> },
+    Let { name: String, value: Box<Expr>, body: Box<Expr> },
+    List(Vec<Expr>),
+    Lambda { params: Vec<String>, body: Box<Expr> },
+    // (guard, value) pairs, tried in order
+    Piecewise(Vec<(Expr, Expr)>),
+    Index { target: Box<Expr>, index: Box<Expr> },
+    Slice { target: Box<Expr>, start: Option<Box<Expr>>, end: Option<Box<Expr>> },
+    FunctionCall { name: String, args: Vec<Expr>, span: Span },
+    Assignment { name: String, value: Box<Expr> },
+    FunctionDef { name: String, params: Vec<String>, body: Box<Expr> },
+    // print(a, b, ...) shows its arguments on one line, separated by spaces;
+    // print() shows an empty line
+    Print(Vec<Expr>),
+    Delete(String),
+}
+
+impl Expr {
+    // Whether two expressions are the same tree, wherever in the source they
+    // came from; `==` also compares the positions.
+    pub fn same_structure(&self, other: &Expr) -> bool {
+        self.without_spans() == other.without_spans()
+    }
+
+    fn without_spans(&self) -> Expr {
+        let strip = |expr: &Expr| Box::new(expr.without_spans());
+        let strip_all = |exprs: &[Expr]| exprs.iter().map(Expr::without_spans).collect();
+        match self {
+            Expr::Variable(name, _) => Expr::Variable(name.clone(), Span::default()),
+            Expr::FunctionCall { name, args, .. } => Expr::FunctionCall { name: name.clone(), args: strip_all(args), span: Span::default() },
+            Expr::UnaryOp { op, expr } => Expr::UnaryOp { op: op.clone(), expr: strip(expr) },
+            Expr::BinaryOp { left, op, right } => Expr::BinaryOp { left: strip(left), op: op.clone(), right: strip(right) },
+            Expr::Conditional { cond, then, otherwise } => {
+                Expr::Conditional { cond: strip(cond), then: strip(then), otherwise: strip(otherwise) }
+            }
+            Expr::Let { name, value, body } => Expr::Let { name: name.clone(), value: strip(value), body: strip(body) },
+            Expr::List(items) => Expr::List(strip_all(items)),
+            Expr::Lambda { params, body } => Expr::Lambda { params: params.clone(), body: strip(body) },
+            Expr::Piecewise(pieces) => Expr::Piecewise(pieces.iter().map(|(guard, value)| (guard.without_spans(), value.without_spans())).collect()),
+            Expr::Index { target, index } => Expr::Index { target: strip(target), index: strip(index) },
+            Expr::Slice { target, start, end } => {
+                Expr::Slice { target: strip(target), start: start.as_deref().map(strip), end: end.as_deref().map(strip) }
+            }
+            Expr::Assignment { name, value } => Expr::Assignment { name: name.clone(), value: strip(value) },
+            Expr::FunctionDef { name, params, body } => Expr::FunctionDef { name: name.clone(), params: params.clone(), body: strip(body) },
+            Expr::Print(exprs) => Expr::Print(strip_all(exprs)),
+            Expr::Number(_) | Expr::Str(_) | Expr::Unit(_) | Expr::Delete(_) => self.clone(),
+        }
+    }
+}
+
+// The text of a string literal with its escapes replaced. An unknown escape
+// stands for the escaped character itself.
+fn unescape(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        out.push(match c {
+            '\\' => match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(other) => other,
+                None => break,
+            },
+            c => c,
+        });
+    }
+    out
+}
+
+fn parse_expr(pair: Pair<Rule>) -> Result<Expr, String> {
+    match pair.as_rule() {
+        Rule::number => parse_number(pair.as_str()).map(Expr::Number),
+        Rule::percentage => {
+            let number = pair.into_inner().next().ok_or("Expected percentage value")?;
+            parse_number(number.as_str()).map(|n| Expr::Number(n / 100.0))
+        }
+        Rule::string => {
+            let inner = pair.into_inner().next().ok_or("Expected string contents")?;
+            Ok(Expr::Str(unescape(inner.as_str())))
+        }
+        Rule::ident => Ok(Expr::Variable(pair.as_str().to_string(), Span::of(&pair))),
+        Rule::quantity => {
+            let mut inner = pair.into_inner();
+            let value = parse_expr(inner.next().ok_or("Expected quantity value")?)?;
+            let unit = Expr::Unit(inner.next().ok_or("Expected unit")?.as_str().to_string());
+            Ok(Expr::BinaryOp { left: Box::new(value), op: "*".to_string(), right: Box::new(unit) })
+        }
+        Rule::function_call => {
+            let span = Span::of(&pair);
+            let mut inner = pair.into_inner();
+            let name = inner.next().ok_or("Expected function name")?.as_str().to_string();
+            let args = inner.map(parse_expr).collect::<Result<_, _>>()?;
+            Ok(Expr::FunctionCall { name, args, span })
+        }
+        Rule::conditional => {
+            let mut inner = pair.into_inner();
+            let cond = parse_expr(inner.next().ok_or("Expected condition")?)?;
+            let Some(then) = inner.next() else { return Ok(cond) };
+            let then = parse_expr(then)?;
+            let otherwise = parse_expr(inner.next().ok_or("Expected else branch")?)?;
+            Ok(Expr::Conditional { cond: Box::new(cond), then: Box::new(then), otherwise: Box::new(otherwise) })
+        }
+        Rule::let_expr => {
+            let mut inner = pair.into_inner().filter(|p| !matches!(p.as_rule(), Rule::let_keyword | Rule::in_keyword));
+            let name = inner.next().ok_or("Expected name after let")?.as_str().to_string();
+            let value = parse_expr(inner.next().ok_or("Expected value after '='")?)?;
+            let body = parse_expr(inner.next().ok_or("Expected body after 'in'")?)?;
+            Ok(Expr::Let { name, value: Box::new(value), body: Box::new(body) })
+        }
+        Rule::lambda => {
+            let mut inner = pair.into_inner();
+            let params = inner.next().ok_or("Expected lambda parameters")?;
+            let params = match params.as_rule() {
+                Rule::ident_list => params.into_inner().map(|p| p.as_str().to_string()).collect(),
+                _ => vec![params.as_str().to_string()],
+            };
+            let body = parse_expr(inner.next().ok_or("Expected lambda body")?)?;
+            Ok(Expr::Lambda { params, body: Box::new(body) })
+        }
+        Rule::piecewise => {
+            let pieces = pair.into_inner().map(|piece| {
+                let mut inner = piece.into_inner();
+                let guard = parse_expr(inner.next().ok_or("Expected piece condition")?)?;
+                let value = parse_expr(inner.next().ok_or("Expected piece value")?)?;
+                Ok((guard, value))
+            });
+            Ok(Expr::Piecewise(pieces.collect::<Result<_, String>>()?))
+        }
+        Rule::list => Ok(Expr::List(pair.into_inner().map(parse_expr).collect::<Result<_, _>>()?)),
+        Rule::unary => {
+            let mut inner = pair.into_inner();
+            let first = inner.next().ok_or("Expected operand")?;
+            if first.as_rule() == Rule::postfix {
+                parse_expr(first)
+            } else {
+                let op = first.as_str().to_string();
+                let expr = parse_expr(inner.next().ok_or("Expected operand after unary operator")?)?;
+                Ok(Expr::UnaryOp { op, expr: Box::new(expr) })
+            }
+        }
+        Rule::postfix => {
+            let mut inner = pair.into_inner();
+            let mut expr = parse_expr(inner.next().ok_or("Expected expression to index")?)?;
+            for suffix in inner {
+                if suffix.as_rule() == Rule::factorial_op {
+                    expr = Expr::UnaryOp { op: "!".to_string(), expr: Box::new(expr) };
+                    continue;
+                }
+                let index = suffix.into_inner().next().ok_or("Expected index")?;
+                let target = Box::new(expr);
+                expr = if index.as_rule() == Rule::slice {
+                    let (mut start, mut end) = (None, None);
+                    for bound in index.into_inner() {
+                        let rule = bound.as_rule();
+                        let value = Box::new(parse_expr(bound.into_inner().next().ok_or("Expected slice bound")?)?);
+                        if rule == Rule::slice_start { start = Some(value) } else { end = Some(value) }
+                    }
+                    Expr::Slice { target, start, end }
+                } else {
+                    Expr::Index { target, index: Box::new(parse_expr(index)?) }
+                };
+            }
+            Ok(expr)
+        }
+        // `^` is right-associative: the grammar nests the exponent as another
+        // power, and any flat chain is still folded from the right
+        Rule::power => {
+            let mut operands = pair
+                .into_inner()
+                .filter(|p| p.as_rule() != Rule::pow_op)
+                .map(parse_expr)
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut expr = operands.pop().ok_or("Expected base expression")?;
+            while let Some(base) = operands.pop() {
+                expr = Expr::BinaryOp { left: Box::new(base), op: "^".to_string(), right: Box::new(expr) };
+            }
+            Ok(expr)
+        }
+        Rule::product | Rule::sum | Rule::comparison | Rule::coalesce => {
+            let mut inner = pair.into_inner();
+            let mut expr = parse_expr(inner.next().ok_or("Expected left-hand expression")?)?;
+            while let Some(op) = inner.next() {
+                let right = parse_expr(inner.next().ok_or("Expected right-hand expression")?)?;
+                let op = if op.as_rule() == Rule::implicit_mul { "*" } else { op.as_str() };
+                expr = Expr::BinaryOp {
+                    left: Box::new(expr),
+                    op: op.to_string(),
+                    right: Box::new(right),
+                };
+            }
+            Ok(expr)
+        }
+        Rule::assignment => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().ok_or("Expected variable name")?.as_str().to_string();
+            let value = parse_expr(inner.next().ok_or("Expected value")?)?;
+            Ok(Expr::Assignment { name, value: Box::new(value) })
+        }
+        Rule::function_def => {
+            let mut inner = pair.into_inner();
+            let name = inner.next().ok_or("Expected function name")?.as_str().to_string();
+            let params = inner
+                .next()
+                .ok_or("Expected function parameters")?
+                .into_inner()
+                .map(|p| p.as_str().to_string())
+                .collect();
+            let body = parse_expr(inner.next().ok_or("Expected function body")?)?;
+            Ok(Expr::FunctionDef { name, params, body: Box::new(body) })
+        }
+        Rule::print_stmt => Ok(Expr::Print(pair.into_inner().map(parse_expr).collect::<Result<_, _>>()?)),
+        Rule::delete_stmt => {
+            let name = pair.into_inner().nth(1).ok_or("Expected name to delete")?.as_str().to_string();
+            Ok(Expr::Delete(name))
+        }
+        Rule::expression | Rule::statement | Rule::primary => {
+            parse_expr(pair.into_inner().next().ok_or("Expected expression")?)
+        }
+        rule => Err(format!("Unexpected rule: {:?}", rule)),
+    }
+}
+
+fn parse_number(text: &str) -> Result<f64, String> {
+    let digits = text.replace('_', "");
+    let radix = match digits.get(..2) {
+        Some("0x") => 16,
+        Some("0b") => 2,
+        Some("0o") => 8,
+        _ => return digits.parse().map_err(|e| format!("Invalid number '{}': {}", text, e)),
+    };
+    u64::from_str_radix(&digits[2..], radix).map(|n| n as f64).map_err(|e| format!("Invalid number '{}': {}", text, e))
+}
+
+// Parses and evaluates `input` against `state`, returning the value of the
+// last statement.
+pub fn evaluate(input: &str, state: &mut State) -> Result<Value, EvalError> {
+    eval_program(&parse_program(input)?, state)?.ok_or_else(|| "Nothing to evaluate".into())
+}
+
+// Parses a single statement into its AST without evaluating it.
+pub fn parse(input: &str) -> Result<Expr, EvalError> {
+    let mut exprs = parse_program(input)?;
+    match exprs.len() {
+        1 => Ok(exprs.remove(0)),
+        n => Err(format!("Expected a single statement, found {}", n).into()),
+    }
+}
+
+// An expression parsed once up front so it can be evaluated many times
+// against different variable bindings without reparsing. It keeps the state
+// it evaluates in, so each evaluation only rebinds the variables.
+pub struct CompiledExpr {
+    expr: Expr,
+    state: RefCell<State>,
+}
+
+impl CompiledExpr {
+    pub fn new(input: &str) -> Result<Self, EvalError> {
+        Ok(CompiledExpr { expr: fold_constants(parse(input)?), state: RefCell::new(State::new()) })
+    }
+
+    pub fn eval_with(&self, vars: &HashMap<String, f64>) -> Result<Value, EvalError> {
+        let mut state = self.state.borrow_mut();
+        // overwrite in place, so that evaluating with the same names again
+        // allocates nothing
+        state.vars.retain(|name, _| vars.contains_key(name));
+        for (name, x) in vars {
+            match state.vars.get_mut(name) {
+                Some(value) => *value = Value::Real(*x),
+                None => {
+                    state.vars.insert(name.clone(), Value::Real(*x));
+                }
+            }
+        }
+        eval(&self.expr, &mut state)
+    }
+}
+
+impl Clone for CompiledExpr {
+    fn clone(&self) -> Self {
+        CompiledExpr { expr: self.expr.clone(), state: RefCell::new(State::new()) }
+    }
+}
+
+impl fmt::Debug for CompiledExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CompiledExpr").field("expr", &self.expr).finish_non_exhaustive()
+    }
+}
+
+pub fn parse_program(input: &str) -> Result<Vec<Expr>, EvalError> {
+    parse_program_debug(input, false).map(|(exprs, _)| exprs)
+}
+
+// One operator node from the pest parse tree, recorded when debugging the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseTrace {
+    pub rule: String,
+    pub start: usize,
+    pub end: usize,
+    pub text: String,
+}
+
+// Formats a parse error rustc-style: the message, the line and column, and
+// the offending source line with a caret under the column.
+pub fn format_parse_error(err: &pest::error::Error<Rule>, source: &str) -> String {
+    let (line, col) = match err.line_col {
+        pest::error::LineColLocation::Pos(pos) => pos,
+        pest::error::LineColLocation::Span(start, _) => start,
+    };
+    let text = source.lines().nth(line - 1).unwrap_or_default();
+    let caret: String = text.chars().take(col - 1).map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+    let gutter = " ".repeat(line.to_string().len());
+    format!(
+        "{}\n{}--> line {}, column {}\n{} |\n{} | {}\n{} | {}^",
+        err.variant.message(),
+        gutter,
+        line,
+        col,
+        gutter,
+        line,
+        text,
+        gutter,
+        caret
+    )
+}
+
+// The raw pest parse of `input` as an indented tree of rules and the text
+// each one matched.
+pub fn token_tree(input: &str) -> Result<String, EvalError> {
+    fn write_pair(pair: Pair<Rule>, depth: usize, out: &mut String) {
+        out.push_str(&format!("{}{:?} {:?}\n", "  ".repeat(depth), pair.as_rule(), pair.as_str()));
+        for inner in pair.into_inner() {
+            write_pair(inner, depth + 1, out);
+        }
+    }
+    let mut out = String::new();
+    for pair in MathParser::parse(Rule::program, input).map_err(|e| EvalError::ParseError(format_parse_error(&e, input)))? {
+        write_pair(pair, 0, &mut out);
+    }
+    Ok(out.trim_end().to_string())
+}
+
+// Whether `input` fails to parse only because it ends too early, e.g. after
+// a trailing operator or an unclosed bracket, so more lines could complete it.
+pub fn is_incomplete(input: &str) -> bool {
+    match MathParser::parse(Rule::program, input) {
+        Ok(_) => false,
+        Err(e) => match e.location {
+            pest::error::InputLocation::Pos(pos) => pos >= input.trim_end().len(),
+            pest::error::InputLocation::Span(_) => false,
+        },
+    }
+}
+
+// Like parse_program, but with `debug_parse` set it also records each operator
+// node (sum, product, power, comparison) and echoes it to stderr.
+pub fn parse_program_debug(input: &str, debug_parse: bool) -> Result<(Vec<Expr>, Vec<ParseTrace>), EvalError> {
+    let program = MathParser::parse(Rule::program, input)
+        .map_err(|e| EvalError::ParseError(format_parse_error(&e, input)))?
+        .next()
+        .ok_or_else(|| EvalError::ParseError("Expected program".to_string()))?;
+    let mut trace = Vec::new();
+    if debug_parse {
+        for pair in program.clone().into_inner().flatten() {
+            if matches!(pair.as_rule(), Rule::power | Rule::product | Rule::sum | Rule::comparison) {
+                eprintln!("{}", pair);
+                let span = pair.as_span();
+                trace.push(ParseTrace {
+                    rule: format!("{:?}", pair.as_rule()),
+                    start: span.start(),
+                    end: span.end(),
+                    text: span.as_str().to_string(),
+                });
+            }
+        }
+    }
+    let exprs = program
+        .into_inner()
+        .filter(|pair| pair.as_rule() != Rule::EOI)
+        .map(parse_expr)
+        .collect::<Result<_, _>>()
+        .map_err(EvalError::ParseError)?;
+    Ok((exprs, trace))
+}
+
+// Evaluates statements in order, stopping at the first error. Returns the
+// value of the last statement, or None for an empty program.
+pub fn eval_program(exprs: &[Expr], state: &mut State) -> Result<Option<Value>, EvalError> {
+    let mut last = None;
+    for expr in exprs {
+        last = Some(eval(expr, state)?);
+    }
+    Ok(last)
+}
+
+fn eval(expr: &Expr, state: &mut State) -> Result<Value, EvalError> {
+    // stack used since the outermost eval, so that deep recursion fails with
+    // an error instead of overflowing whatever thread the caller runs on
+    let marker = 0u8;
+    let here = &marker as *const u8 as usize;
+    let outermost = state.stack_base.is_none();
+    if state.stack_base.get_or_insert(here).abs_diff(here) > state.max_stack_bytes {
+        return Err("maximum recursion depth exceeded".into());
+    }
+    // literals, names and definitions reduce to nothing worth showing
+    let traced = state.trace
+        && !matches!(
+            expr,
+            Expr::Number(_) | Expr::Str(_) | Expr::Variable(..) | Expr::Unit(_) | Expr::Lambda { .. } | Expr::FunctionDef { .. } | Expr::Delete(_)
+        );
+    if traced {
+        state.trace_depth += 1;
+    }
+    let value = eval_node(expr, state);
+    if outermost {
+        state.stack_base = None;
+    }
+    if traced {
+        state.trace_depth -= 1;
+        if let Ok(value) = &value {
+            println!("{}{} = {}", "  ".repeat(state.trace_depth), expr_to_string(expr), state.format_value(value));
+        }
+    }
+    let value = value?;
+    if state.strict_finite && !value.is_finite() {
+        return Err("result is not finite".into());
+    }
+    Ok(value)
+}
+
+fn eval_node(expr: &Expr, state: &mut State) -> Result<Value, EvalError> {
+    match expr {
+        Expr::Number(n) if state.decimal => Ok(Value::decimal(*n)),
+        Expr::Number(n) if state.exact => Ok(Value::exact(*n)),
+        Expr::Number(n) => Ok(Value::Real(*n)),
+        Expr::Str(text) => Ok(Value::Str(text.clone())),
+        // variables and parameters shadow the built-in constants
+        Expr::Variable(name, span) => match state.lookup(name) {
+            Some(value) => Ok(value),
+            // a bare function name is a reference to that function
+            None if constant(name).is_none() && state.is_function(name) => Ok(Value::Function(name.clone())),
+            None => constant(name).ok_or_else(|| {
+                let scope = state.scopes.last().into_iter().flat_map(|frame| frame.keys());
+                let candidates = scope.chain(state.vars.keys()).map(String::as_str).chain(CONSTANTS.iter().copied());
+                let suggestion = did_you_mean(name, candidates);
+                EvalError::UndefinedVariable { name: name.clone(), suggestion, span: (span.line > 0).then_some(*span) }
+            }),
+        },
+        // only the taken branch is evaluated
+        Expr::Conditional { cond, then, otherwise } => {
+            if eval(cond, state)?.is_truthy() { eval(then, state) } else { eval(otherwise, state) }
+        }
+        Expr::UnaryOp { op, expr } => {
+            let val = eval(expr, state)?;
+            match op.as_str() {
+                "+" => Ok(val),
+                "-" => match val {
+                    Value::List(_) | Value::Matrix(_) | Value::Str(_) => binary_op(&Value::Real(0.0), "-", &val, state.ieee_division),
+                    Value::Rational(num, den) if num != i64::MIN => Ok(Value::Rational(-num, den)),
+                    Value::Quantity(x, dims) => Ok(Value::Quantity(-x, dims)),
+                    Value::Decimal(mantissa, scale) if mantissa != i128::MIN => Ok(Value::Decimal(-mantissa, scale)),
+                    _ => {
+                        let (re, im) = val.parts();
+                        Ok(Value::complex(-re, -im))
+                    }
+                },
+                "!" => factorial(val.as_real()?).map(Value::Real),
+                _ => Err(format!("Unknown unary operator: {}", op).into()),
+            }
+        }
+        // the fallback is only evaluated when the left side names something
+        // undefined; any other error still propagates
+        Expr::BinaryOp { left, op, right } if op == "??" => match eval(left, state) {
+            Err(EvalError::UndefinedVariable { .. }) => eval(right, state),
+            result => result,
+        },
+        Expr::BinaryOp { left, op, right } => {
+            let l = eval(left, state)?;
+            let r = eval(right, state)?;
+            binary_op(&l, op, &r, state.ieee_division)
+        }
+        Expr::FunctionCall { name, args, .. } if SPECIAL_FORMS.contains(&name.as_str()) || (name == "sum" && args.len() != 1) => {
+            // a function named as an argument, as in diff(f, 1), is the one to
+            // point at if it turns out not to exist
+            eval_special(name, args, state).map_err(|e| match e {
+                EvalError::UnknownFunction { name: unknown, suggestion, span: None } => {
+                    let span = args.iter().find_map(|arg| match arg {
+                        Expr::Variable(name, span) if *name == unknown && span.line > 0 => Some(*span),
+                        _ => None,
+                    });
+                    EvalError::UnknownFunction { name: unknown, suggestion, span }
+                }
+                e => e,
+            })
+        }
+        Expr::FunctionCall { name, args, span } => {
+            if state.lookup_callable(name).is_none()
+                && let Some(func) = state.funcs.get(name)
+            {
+                check_arity(name, func.0.len(), args.len())?;
+            }
+            let values = args.iter().map(|a| eval(a, state)).collect::<Result<Vec<_>, _>>()?;
+            // the call that named the function is the one to point at
+            call_function(name, &values, state).map_err(|e| match e {
+                EvalError::UnknownFunction { name: unknown, suggestion, span: None } if unknown == *name => {
+                    EvalError::UnknownFunction { name: unknown, suggestion, span: (span.line > 0).then_some(*span) }
+                }
+                e => e,
+            })
+        }
+        _ => eval_structure(expr, state),
+    }
+}
+
+// Everything but the names, operators and calls that nearly every evaluation
+// goes through, kept apart so that eval_node stays cheap to recurse into.
+fn eval_structure(expr: &Expr, state: &mut State) -> Result<Value, EvalError> {
+    match expr {
+        // a variable of the same name takes the place of the unit, so `2h`
+        // still multiplies when h is defined
+        Expr::Unit(name) => match state.lookup(name) {
+            Some(value) => Ok(value),
+            None => unit_value(name),
+        },
+        Expr::List(items) => {
+            let items: Vec<Value> = items.iter().map(|item| eval(item, state)).collect::<Result<_, _>>()?;
+            // a list of equal-length rows of numbers is a matrix
+            Ok(matrix::list_or_matrix(items))
+        }
+        Expr::Lambda { params, body } => Ok(Value::Lambda(Rc::new(value::Lambda {
+            params: params.clone(),
+            body: (**body).clone(),
+            captured: state.scopes.last().cloned().unwrap_or_default(),
+        }))),
+        // the binding lives in a new frame on top of the current one, so it
+        // shadows outer names and disappears once the body is done
+        Expr::Let { name, value, body } => {
+            let value = eval(value, state)?;
+            if state.scopes.len() >= state.max_call_depth {
+                return Err("maximum recursion depth exceeded".into());
+            }
+            let mut frame = state.scopes.last().cloned().unwrap_or_default();
+            frame.insert(name.clone(), value);
+            state.scopes.push(frame);
+            let result = eval(body, state);
+            state.scopes.pop();
+            result
+        }
+        Expr::Piecewise(pieces) => {
+            for (guard, value) in pieces {
+                if eval(guard, state)?.is_truthy() {
+                    return eval(value, state);
+                }
+            }
+            Err("no matching piece".into())
+        }
+        Expr::Index { target, index } => {
+            // a matrix indexes and slices by row
+            let Some(items) = list_items(eval(target, state)?) else {
+                return Err("Only lists and matrices can be indexed".into());
+            };
+            let index = as_integer("index", eval_real(index, state)?)?;
+            let position = if index < 0 { items.len() as i64 + index } else { index };
+            usize::try_from(position)
+                .ok()
+                .and_then(|i| items.get(i).cloned())
+                .ok_or_else(|| "index out of bounds".into())
+        }
+        Expr::Slice { target, start, end } => {
+            let Some(items) = list_items(eval(target, state)?) else {
+                return Err("Only lists and matrices can be sliced".into());
+            };
+            // like Python, negative bounds count from the end and out-of-range
+            // bounds are clamped
+            let mut bound = |expr: &Option<Box<Expr>>, default: usize| -> Result<usize, EvalError> {
+                let Some(expr) = expr else { return Ok(default) };
+                let i = as_integer("slice", eval_real(expr, state)?)?;
+                let i = if i < 0 { items.len() as i64 + i } else { i };
+                Ok(i.clamp(0, items.len() as i64) as usize)
+            };
+            let (start, end) = (bound(start, 0)?, bound(end, items.len())?);
+            Ok(matrix::list_or_matrix(items.get(start..end).unwrap_or_default().to_vec()))
+        }
+        Expr::Assignment { name, value } => {
+            let val = eval(value, state)?;
+            state.assign(name.clone(), val.clone());
+            Ok(val)
+        }
+        Expr::FunctionDef { name, params, body } => {
+            state.define(name.clone(), params.clone(), (**body).clone());
+            Ok(Value::Real(0.0)) // or just acknowledge
+        }
+        Expr::Delete(name) => {
+            let removed_var = state.vars.remove(name).is_some();
+            let removed_func = state.funcs.remove(name).is_some();
+            if !removed_var && !removed_func {
+                return Err(format!("Nothing named '{}' to delete", name).into());
+            }
+            state.clear_memo();
+            Ok(Value::Real(0.0))
+        }
+        Expr::Print(exprs) => {
+            let values = exprs.iter().map(|expr| eval(expr, state)).collect::<Result<Vec<_>, _>>()?;
+            println!("{}", values.iter().map(|value| state.format_value(value)).collect::<Vec<_>>().join(" "));
+            Ok(values.into_iter().last().unwrap_or(Value::Real(0.0)))
+        }
+        _ => unreachable!("evaluated by eval_node"),
+    }
+}
+
+// Special forms get their arguments unevaluated. They live outside eval_node
+// to keep its stack frame, which every nested evaluation pays for, small.
+fn eval_special(name: &str, args: &[Expr], state: &mut State) -> Result<Value, EvalError> {
+    match name {
+        // only the taken branch is evaluated
+        "if" => {
+            let [cond, then, otherwise] = args else {
+                return Err(format!("if expects 3 arguments (cond, then, else), got {}", args.len()).into());
+            };
+            if eval(cond, state)?.is_truthy() {
+                eval(then, state)
+            } else {
+                eval(otherwise, state)
+            }
+        }
+        // special form: while(cond, body...) re-evaluates the body expressions in
+        // order until cond is false, returning the last body value (0 if the
+        // body never ran)
+        "while" => {
+            let [cond, body @ ..] = args else {
+                return Err("while expects a condition and a body, e.g. while(i < 10, i = i + 1)".into());
+            };
+            let mut last = Value::Real(0.0);
+            let mut iterations = 0;
+            while eval(cond, state)?.is_truthy() {
+                if iterations == state.max_loop_iterations {
+                    return Err(format!("while exceeded {} iterations", state.max_loop_iterations).into());
+                }
+                iterations += 1;
+                for expr in body {
+                    last = eval(expr, state)?;
+                }
+            }
+            Ok(last)
+        }
+        // the argument is a name to look up, not an expression to evaluate
+        "defined" => {
+            let [Expr::Variable(target, _)] = args else {
+                return Err("defined expects a name, e.g. defined(x)".into());
+            };
+            let known = state.lookup(target).is_some()
+                || constant(target).is_some()
+                || state.is_function(target)
+                || SPECIAL_FORMS.contains(&target.as_str());
+            Ok(Value::Real(bool_to_f64(known)))
+        }
+        // special forms: the right operand is skipped once the result is known
+        "and" | "or" => {
+            let [left, right] = args else {
+                return Err(format!("{} expects 2 arguments, got {}", name, args.len()).into());
+            };
+            let l = eval(left, state)?.is_truthy();
+            if l == (name == "or") {
+                return Ok(Value::Real(bool_to_f64(l)));
+            }
+            Ok(Value::Real(bool_to_f64(eval(right, state)?.is_truthy())))
+        }
+        "diff" => {
+            let [Expr::Variable(func, _), x] = args else {
+                return Err("diff expects a function name and a point, e.g. diff(f, 3)".into());
+            };
+            let x0 = eval_real(x, state)?;
+            differential::derivative(|x| call_real(func, x, state), x0).map(Value::Real)
+        }
+        "integrate" => {
+            let [Expr::Variable(func, _), a, b] = args else {
+                return Err("integrate expects a function name and bounds, e.g. integrate(f, 0, 1)".into());
+            };
+            let (a, b) = (eval_real(a, state)?, eval_real(b, state)?);
+            differential::integrate(|x| call_real(func, x, state), a, b, INTEGRATION_TOLERANCE).map(Value::Real)
+        }
+        "solve" => {
+            let [Expr::Variable(func, _), guess] = args else {
+                return Err("solve expects a function name and a starting guess, e.g. solve(f, 1)".into());
+            };
+            let guess = eval_real(guess, state)?;
+            differential::solve(|x| call_real(func, x, state), guess, SOLVE_MAX_ITERATIONS).map(Value::Real)
+        }
+        "limit" => {
+            let [Expr::Variable(func, _), x0] = args else {
+                return Err("limit expects a function name and a point, e.g. limit(f, 0)".into());
+            };
+            let x0 = eval_real(x0, state)?;
+            differential::limit(|x| call_real(func, x, state), x0, LIMIT_TOLERANCE).map(Value::Real)
+        }
+        // special form: partial(f, x, a, b) is df/dx at the point (a, b)
+        "partial" => {
+            let [Expr::Variable(func, span), Expr::Variable(var, _), point @ ..] = args else {
+                return Err("partial expects a function name, a parameter and a point, e.g. partial(f, x, 1, 2)".into());
+            };
+            let params = user_function("partial", func, span, state)?.0.clone();
+            let index = params
+                .iter()
+                .position(|param| param == var)
+                .ok_or_else(|| format!("'{}' is not a parameter of '{}'", var, func))?;
+            check_arity(func, params.len(), point.len())?;
+            let point = point.iter().map(|coord| eval_real(coord, state)).collect::<Result<Vec<_>, _>>()?;
+            differential::partial(|at| call_reals(func, at, state), &point, index).map(Value::Real)
+        }
+        // special form: gradient(f, a, b) is [df/dx, df/dy] at (a, b), in
+        // parameter order
+        "gradient" => {
+            let [Expr::Variable(func, span), point @ ..] = args else {
+                return Err("gradient expects a function name and a point, e.g. gradient(f, 1, 2)".into());
+            };
+            let arity = user_function("gradient", func, span, state)?.0.len();
+            check_arity(func, arity, point.len())?;
+            let point = point.iter().map(|coord| eval_real(coord, state)).collect::<Result<Vec<_>, _>>()?;
+            let gradient = differential::gradient(|at| call_reals(func, at, state), &point)?;
+            Ok(Value::List(gradient.into_iter().map(Value::Real).collect()))
+        }
+        // sum(list) is the list reduction built-in
+        "sum" => {
+            let [Expr::Variable(func, _), lo, hi] = args else {
+                return Err("sum expects a function name and integer bounds, e.g. sum(f, 1, 10)".into());
+            };
+            let (lo, hi) = (as_integer("sum", eval_real(lo, state)?)?, as_integer("sum", eval_real(hi, state)?)?);
+            (lo..=hi).try_fold(Value::Real(0.0), |acc, i| binary_op(&acc, "+", &call_function(func, &[Value::Real(i as f64)], state)?, state.ieee_division))
+        }
+        "prod" => {
+            let [Expr::Variable(func, _), lo, hi] = args else {
+                return Err("prod expects a function name and integer bounds, e.g. prod(f, 1, 10)".into());
+            };
+            let (lo, hi) = (as_integer("prod", eval_real(lo, state)?)?, as_integer("prod", eval_real(hi, state)?)?);
+            (lo..=hi).try_fold(Value::Real(1.0), |acc, i| binary_op(&acc, "*", &call_function(func, &[Value::Real(i as f64)], state)?, state.ieee_division))
+        }
+        // special forms: the function is a name (built-in, user or lambda
+        // variable) or an inline lambda
+        "map" | "filter" => {
+            let [func, list] = args else {
+                return Err(format!("{} expects a function and a list, e.g. {}(f, [1, 2, 3])", name, name).into());
+            };
+            let Some(items) = list_items(eval(list, state)?) else {
+                return Err(format!("{} expects a list as its second argument", name).into());
+            };
+            let mut results = Vec::new();
+            for item in items {
+                let result = apply(func, std::slice::from_ref(&item), state)?;
+                match name {
+                    "map" => results.push(result),
+                    _ if result.is_truthy() => results.push(item),
+                    _ => {}
+                }
+            }
+            Ok(matrix::list_or_matrix(results))
+        }
+        "reduce" => {
+            let [func, init, list] = args else {
+                return Err("reduce expects a function, an initial value and a list, e.g. reduce(f, 0, [1, 2])".into());
+            };
+            let init = eval(init, state)?;
+            let Some(items) = list_items(eval(list, state)?) else {
+                return Err("reduce expects a list as its third argument".into());
+            };
+            items.into_iter().try_fold(init, |acc, item| apply(func, &[acc, item], state))
+        }
+        // special form: taylor(f, x0, n) defines f_taylor, the degree-n Taylor
+        // polynomial of f around x0, from repeated symbolic derivatives
+        "taylor" => {
+            let [Expr::Variable(func, span), x0, n] = args else {
+                return Err("taylor expects a function name, a point and an order, e.g. taylor(f, 0, 3)".into());
+            };
+            let x0 = eval_real(x0, state)?;
+            let n = as_integer("taylor", eval_real(n, state)?)?;
+            if n < 0 {
+                return Err(format!("taylor order must be non-negative, got {}", n).into());
+            }
+            let (param, mut term) = match state.funcs.get(func) {
+                Some(def) if def.0.len() == 1 => (def.0[0].clone(), def.1.clone()),
+                Some(_) => return Err(format!("taylor needs a single-argument function, '{}' is not", func).into()),
+                None if !BUILTIN_FUNCTIONS.contains(&func.as_str()) && !state.funcs_native.contains_key(func) => {
+                    return Err(unknown_function(func, span, state));
+                }
+                None => ("x".to_string(), Expr::FunctionCall { name: func.clone(), args: vec![Expr::Variable("x".to_string(), Span::default())], span: Span::default() }),
+            };
+            let offset = Expr::BinaryOp {
+                left: Box::new(Expr::Variable(param.clone(), Span::default())),
+                op: "-".to_string(),
+                right: Box::new(Expr::Number(x0)),
+            };
+            let mut poly = Expr::Number(0.0);
+            for k in 0..=n {
+                if k > 0 {
+                    term = differential::differentiate(&term, &param)?;
+                }
+                state.scopes.push(HashMap::from([(param.clone(), Value::Real(x0))]));
+                let value = eval(&term, state);
+                state.scopes.pop();
+                let coeff = value?.as_real()? / factorial(k as f64)?;
+                let monomial = Expr::BinaryOp {
+                    left: Box::new(Expr::Number(coeff)),
+                    op: "*".to_string(),
+                    right: Box::new(Expr::BinaryOp {
+                        left: Box::new(offset.clone()),
+                        op: "^".to_string(),
+                        right: Box::new(Expr::Number(k as f64)),
+                    }),
+                };
+                poly = Expr::BinaryOp { left: Box::new(poly), op: "+".to_string(), right: Box::new(monomial) };
+            }
+            state.define(format!("{}_taylor", func), vec![param], poly);
+            Ok(Value::Real(0.0))
+        }
+        // special form: deriv(f) or deriv(f, var) defines f' symbolically
+        "deriv" => {
+            let (func, span, var) = match args {
+                [Expr::Variable(func, span)] => (func, span, None),
+                [Expr::Variable(func, span), Expr::Variable(var, _)] => (func, span, Some(var)),
+                _ => return Err("deriv expects a function name and optional variable, e.g. deriv(f)".into()),
+            };
+            let def = user_function("deriv", func, span, state)?;
+            let (params, body) = &*def;
+            let var = match var {
+                Some(var) if !params.contains(var) => {
+                    return Err(format!("'{}' is not a parameter of '{}'", var, func).into());
+                }
+                Some(var) => var,
+                None => params.first().ok_or_else(|| format!("Function '{}' has no parameters", func))?,
+            };
+            let derived = dmath::simplify(&differential::differentiate(body, var)?);
+            state.define(format!("{}'", func), params.clone(), derived);
+            Ok(Value::Real(0.0))
+        }
+        _ => unreachable!("{} is not a special form", name),
+    }
+}
+
+fn constant(name: &str) -> Option<Value> {
+    match name {
+        "pi" => Some(Value::Real(std::f64::consts::PI)),
+        "e" => Some(Value::Real(std::f64::consts::E)),
+        "tau" => Some(Value::Real(std::f64::consts::TAU)),
+        "phi" => Some(Value::Real((1.0 + 5f64.sqrt()) / 2.0)),
+        "inf" => Some(Value::Real(f64::INFINITY)),
+        "nan" => Some(Value::Real(f64::NAN)),
+        // the imaginary unit
+        "i" => Some(Value::Complex(0.0, 1.0)),
+        _ => None,
+    }
+}
+
+// One of the unit `name`, in SI base units.
+fn unit_value(name: &str) -> Result<Value, EvalError> {
+    let (factor, dims) = units::parse_unit(name).ok_or_else(|| format!("Unknown unit: {}", name))?;
+    Ok(Value::quantity(factor, dims))
+}
+
+// The items of a list, or the rows of a matrix as lists.
+fn list_items(value: Value) -> Option<Vec<Value>> {
+    match value {
+        Value::List(items) => Some(items),
+        Value::Matrix(m) => Some(matrix::to_rows(&m)),
+        _ => None,
+    }
+}
+
+fn eval_real(expr: &Expr, state: &mut State) -> Result<f64, EvalError> {
+    eval(expr, state)?.as_real()
+}
+
+fn binary_op(l: &Value, op: &str, r: &Value, ieee_division: bool) -> Result<Value, EvalError> {
+    match (l, r) {
+        (Value::Lambda(_) | Value::Function(_), _) | (_, Value::Lambda(_) | Value::Function(_)) => Err(format!("Operator '{}' cannot be applied to a function", op).into()),
+        // `+` joins text, so "x = " + 3 is "x = 3"
+        (Value::Str(_), _) | (_, Value::Str(_)) if op == "+" => Ok(Value::Str(format!("{}{}", l, r))),
+        (Value::Str(a), Value::Str(b)) if op == "==" || op == "!=" => Ok(Value::Real(bool_to_f64((a == b) == (op == "==")))),
+        (Value::Str(_), _) | (_, Value::Str(_)) => Err(format!("Operator '{}' cannot be applied to a string", op).into()),
+        (Value::Matrix(_), _) | (_, Value::Matrix(_)) => matrix::matrix_op(l, op, r, ieee_division),
+        // lists combine element-wise, and a scalar is applied to every element
+        (Value::List(ls), Value::List(rs)) if ls.len() != rs.len() => {
+            Err(format!("List length mismatch: {} vs {}", ls.len(), rs.len()).into())
+        }
+        (Value::List(ls), Value::List(rs)) => {
+            ls.iter().zip(rs).map(|(l, r)| binary_op(l, op, r, ieee_division)).collect::<Result<_, _>>().map(Value::List)
+        }
+        (Value::List(ls), r) => ls.iter().map(|l| binary_op(l, op, r, ieee_division)).collect::<Result<_, _>>().map(Value::List),
+        (l, Value::List(rs)) => rs.iter().map(|r| binary_op(l, op, r, ieee_division)).collect::<Result<_, _>>().map(Value::List),
+        (Value::Quantity(..), _) | (_, Value::Quantity(..)) => quantity_op(l, op, r, ieee_division),
+        (_, r) if op == "/" && !ieee_division && r.parts() == (0.0, 0.0) => Err(EvalError::DivByZero),
+        // a negative base with a fractional exponent has a complex result
+        (Value::Real(l), Value::Real(r)) if op == "^" && *l < 0.0 && r.fract() != 0.0 => {
+            let (re, im) = value::complex_pow((*l, 0.0), (*r, 0.0));
+            Ok(Value::complex(re, im))
+        }
+        (Value::Real(l), Value::Real(r)) => real_op(*l, op, *r).map(Value::Real),
+        (Value::Decimal(..), _) | (_, Value::Decimal(..)) if let (Some(a), Some(b)) = (l.as_decimal(), r.as_decimal()) => {
+            match value::decimal_op(a, op, b) {
+                Some(value) => Ok(value),
+                None => {
+                    warn_float_fallback(&format!("{} {} {}", l, op, r));
+                    binary_op(&Value::Real(l.as_real()?), op, &Value::Real(r.as_real()?), ieee_division)
+                }
+            }
+        }
+        (Value::Rational(a, b), Value::Rational(c, d)) => match value::rational_op((*a, *b), op, (*c, *d)) {
+            Some(value) => Ok(value),
+            None => binary_op(&Value::Real(l.as_real()?), op, &Value::Real(r.as_real()?), ieee_division),
+        },
+        (Value::Real(_) | Value::Rational(..) | Value::Decimal(..), Value::Real(_) | Value::Rational(..) | Value::Decimal(..)) => {
+            binary_op(&Value::Real(l.as_real()?), op, &Value::Real(r.as_real()?), ieee_division)
+        }
+        _ => {
+            let (a, b) = (l.parts(), r.parts());
+            let (re, im) = match op {
+                "+" => (a.0 + b.0, a.1 + b.1),
+                "-" => (a.0 - b.0, a.1 - b.1),
+                "*" => value::complex_mul(a, b),
+                "/" => value::complex_div(a, b),
+                "^" => value::complex_pow(a, b),
+                "==" => return Ok(Value::Real(bool_to_f64(approx_eq(a.0, b.0) && approx_eq(a.1, b.1)))),
+                "!=" => return Ok(Value::Real(bool_to_f64(!(approx_eq(a.0, b.0) && approx_eq(a.1, b.1))))),
+                _ => return Err(format!("Operator '{}' is not defined for complex numbers", op).into()),
+            };
+            Ok(Value::complex(re, im))
+        }
+    }
+}
+
+// Arithmetic where at least one side has units. Magnitudes are already in SI
+// base units, so only the dimensions need checking or combining.
+fn quantity_op(l: &Value, op: &str, r: &Value, ieee_division: bool) -> Result<Value, EvalError> {
+    let split = |value: &Value| -> Result<_, EvalError> {
+        match value {
+            Value::Quantity(x, dims) => Ok((*x, *dims)),
+            _ => Ok((value.as_real()?, units::DIMENSIONLESS)),
+        }
+    };
+    let ((a, da), (b, db)) = (split(l)?, split(r)?);
+    let name = |dims: &units::Dims| match units::format_dims(dims) {
+        unit if unit.is_empty() => "dimensionless".to_string(),
+        unit => unit,
+    };
+    let dims = match op {
+        "*" => units::combine(da, db, 1).ok_or("unit exponent out of range")?,
+        "/" if b == 0.0 && !ieee_division => return Err(EvalError::DivByZero),
+        "/" => units::combine(da, db, -1).ok_or("unit exponent out of range")?,
+        "^" if db != units::DIMENSIONLESS => return Err(format!("Exponent must be dimensionless, got {}", name(&db)).into()),
+        "^" => {
+            let exps = da.map(|d| d as f64 * b);
+            if exps.iter().any(|e| e.fract() != 0.0 || e.abs() > i8::MAX as f64) {
+                return Err(format!("Cannot raise {} to the power {}", name(&da), b).into());
+            }
+            exps.map(|e| e as i8)
+        }
+        _ if da != db => return Err(format!("Incompatible units: {} and {}", name(&da), name(&db)).into()),
+        "==" | "!=" | "<" | ">" | "<=" | ">=" => return real_op(a, op, b).map(Value::Real),
+        _ => da,
+    };
+    Ok(Value::quantity(real_op(a, op, b)?, dims))
+}
+
+fn real_op(l: f64, op: &str, r: f64) -> Result<f64, EvalError> {
+    match op {
+        "+" => Ok(l + r),
+        "-" => Ok(l - r),
+        "*" => Ok(l * r),
+        "/" => Ok(l / r),
+        "%" => euclid_mod(l, r),
+        "^" => Ok(l.powf(r)),
+        "==" => Ok(bool_to_f64(approx_eq(l, r))),
+        "!=" => Ok(bool_to_f64(!approx_eq(l, r))),
+        "<" => Ok(bool_to_f64(l < r && !approx_eq(l, r))),
+        ">" => Ok(bool_to_f64(l > r && !approx_eq(l, r))),
+        "<=" => Ok(bool_to_f64(l < r || approx_eq(l, r))),
+        ">=" => Ok(bool_to_f64(l > r || approx_eq(l, r))),
+        _ => Err(format!("Unknown operator: {}", op).into()),
+    }
+}
+
+enum Tail {
+    Done(Value),
+    // arguments for another call of the same function
+    Call(Vec<Value>),
+}
+
+// Evaluates the body of user function `func`, but stops at a call to `func`
+// itself in tail position and returns its arguments instead. Tail positions
+// are the body and the branches of conditionals, `if` and piecewise.
+fn eval_tail(expr: &Expr, func: &str, state: &mut State) -> Result<Tail, EvalError> {
+    match expr {
+        Expr::Conditional { cond, then, otherwise } => {
+            let branch = if eval(cond, state)?.is_truthy() { then } else { otherwise };
+            eval_tail(branch, func, state)
+        }
+        Expr::FunctionCall { name, args, .. } if name == "if" && args.len() == 3 => {
+            let branch = if eval(&args[0], state)?.is_truthy() { &args[1] } else { &args[2] };
+            eval_tail(branch, func, state)
+        }
+        Expr::Piecewise(pieces) => {
+            for (guard, value) in pieces {
+                if eval(guard, state)?.is_truthy() {
+                    return eval_tail(value, func, state);
+                }
+            }
+            Err("no matching piece".into())
+        }
+        // a variable holding a function would shadow `func` here
+        Expr::FunctionCall { name, args, .. } if name == func && state.lookup_callable(name).is_none() => {
+            Ok(Tail::Call(args.iter().map(|arg| eval(arg, state)).collect::<Result<_, _>>()?))
+        }
+        _ => eval(expr, state).map(Tail::Done),
+    }
+}
+
+// Cache key for a call whose arguments are all numbers, or None. Debug
+// formatting keeps exact and floating values apart and gives every NaN the
+// same key. Settings that change results are part of the key.
+fn memo_key(name: &str, values: &[Value], state: &State) -> Option<String> {
+    let numeric = |value: &Value| {
+        matches!(value, Value::Real(_) | Value::Rational(..) | Value::Complex(..) | Value::Decimal(..) | Value::Quantity(..))
+    };
+    values.iter().all(numeric).then(|| {
+        let settings = (state.angle_mode, state.exact, state.decimal, state.ieee_division, state.strict_finite);
+        format!("{}{:?}{:?}", name, values, settings)
+    })
+}
+
+// Whether user function `name` always gives the same result for the same
+// arguments: its body reads only parameters and constants, has no side
+// effects, and calls only built-ins and other pure functions.
+fn is_pure(name: &str, state: &mut State) -> bool {
+    if let Some(pure) = state.pure_funcs.get(name) {
+        return *pure;
+    }
+    // assumed pure while its body is checked, so recursion terminates
+    state.pure_funcs.insert(name.to_string(), true);
+    let pure = match state.funcs.get(name).cloned() {
+        Some(func) => is_pure_expr(&func.1, &func.0, state),
+        None => BUILTIN_FUNCTIONS.contains(&name) && !IMPURE_BUILTINS.contains(&name) && !state.funcs_native.contains_key(name),
+    };
+    if !pure {
+        // functions checked meanwhile may have relied on the assumption
+        state.pure_funcs.clear();
+    }
+    state.pure_funcs.insert(name.to_string(), pure);
+    pure
+}
+
+fn is_pure_expr(expr: &Expr, params: &[String], state: &mut State) -> bool {
+    let bound = |name: &String| params.contains(name);
+    let global = |name: &String, state: &State| state.vars.contains_key(name);
+    match expr {
+        Expr::Number(_) | Expr::Str(_) => true,
+        Expr::Variable(name, _) => bound(name) || (constant(name).is_some() && !global(name, state)),
+        Expr::Unit(name) => bound(name) || !global(name, state),
+        Expr::UnaryOp { expr, .. } => is_pure_expr(expr, params, state),
+        Expr::BinaryOp { left, right, .. } => is_pure_expr(left, params, state) && is_pure_expr(right, params, state),
+        Expr::Conditional { cond, then, otherwise } => [cond, then, otherwise].iter().all(|e| is_pure_expr(e, params, state)),
+        Expr::Let { name, value, body } => {
+            let inner: Vec<String> = params.iter().cloned().chain([name.clone()]).collect();
+            is_pure_expr(value, params, state) && is_pure_expr(body, &inner, state)
+        }
+        Expr::Lambda { params: own, body } => {
+            let inner: Vec<String> = params.iter().chain(own).cloned().collect();
+            is_pure_expr(body, &inner, state)
+        }
+        Expr::List(items) => items.iter().all(|item| is_pure_expr(item, params, state)),
+        Expr::Piecewise(pieces) => {
+            pieces.iter().all(|(guard, value)| is_pure_expr(guard, params, state) && is_pure_expr(value, params, state))
+        }
+        Expr::Index { target, index } => is_pure_expr(target, params, state) && is_pure_expr(index, params, state),
+        Expr::Slice { target, start, end } => {
+            is_pure_expr(target, params, state) && start.iter().chain(end).all(|bound| is_pure_expr(bound, params, state))
+        }
+        Expr::FunctionCall { name, args, .. } => {
+            let callee = !bound(name) && (SPECIAL_FORMS.contains(&name.as_str()) || is_pure(name, state));
+            callee && args.iter().all(|arg| is_pure_expr(arg, params, state))
+        }
+        Expr::Assignment { .. } | Expr::FunctionDef { .. } | Expr::Print(_) | Expr::Delete(_) => false,
+    }
+}
+
+// The error for calling `name` when nothing of that name can be called,
+// suggesting the closest name that can. `span` is where the name was
+// written, if it came from the source.
+fn unknown_function(name: &str, span: &Span, state: &State) -> EvalError {
+    let callables = state.vars.iter().filter(|(_, value)| matches!(value, Value::Lambda(_) | Value::Function(_)));
+    let candidates = state.funcs.keys().chain(state.funcs_native.keys()).chain(callables.map(|(name, _)| name));
+    let candidates = candidates.map(String::as_str).chain(BUILTIN_FUNCTIONS.iter().chain(SPECIAL_FORMS).copied());
+    EvalError::UnknownFunction { name: name.to_string(), suggestion: did_you_mean(name, candidates), span: (span.line > 0).then_some(*span) }
+}
+
+// The definition of user function `name`, given to special form `form`,
+// which needs its parameter names and body.
+fn user_function(form: &str, name: &str, span: &Span, state: &State) -> Result<Rc<(Vec<String>, Expr)>, EvalError> {
+    if let Some(def) = state.funcs.get(name) {
+        return Ok(def.clone());
+    }
+    if BUILTIN_FUNCTIONS.contains(&name) || state.funcs_native.contains_key(name) {
+        return Err(format!("{} needs a user-defined function, '{}' is built in", form, name).into());
+    }
+    Err(unknown_function(name, span, state))
+}
+
+fn check_arity(name: &str, expected: usize, got: usize) -> Result<(), EvalError> {
+    if expected != got {
+        return Err(EvalError::ArityMismatch { name: name.to_string(), expected, got });
+    }
+    Ok(())
+}
+
+// Calls a user-defined, native or built-in function (in that order of
+// precedence) with already-evaluated arguments.
+fn call_function(name: &str, values: &[Value], state: &mut State) -> Result<Value, EvalError> {
+    // a variable holding a function shadows functions of the same name
+    match state.lookup_callable(name) {
+        Some(Value::Lambda(lambda)) => call_lambda(name, &lambda, values, state),
+        Some(Value::Function(target)) => call_named(&target, values, state),
+        _ => call_named(name, values, state),
+    }
+}
+
+fn call_named(name: &str, values: &[Value], state: &mut State) -> Result<Value, EvalError> {
+    if let Some(func) = state.funcs.get(name).cloned() { // cheap Rc clone to release the borrow on state
+        let (params, body) = &*func;
+        check_arity(name, params.len(), values.len())?;
+        let key = memo_key(name, values, state).filter(|_| state.memoize && is_pure(name, state));
+        if let Some(value) = key.as_ref().and_then(|key| state.memo.get(key)) {
+            return Ok(value.clone());
+        }
+        if state.scopes.len() >= state.max_call_depth {
+            return Err("maximum recursion depth exceeded".into());
+        }
+        // a self-call in tail position reuses this frame instead of recursing
+        let mut args = values.to_vec();
+        let mut tail_calls = 0;
+        let max_tail_calls = state.max_call_depth.saturating_mul(TAIL_CALLS_PER_FRAME);
+        let result = loop {
+            state.scopes.push(params.iter().cloned().zip(args).collect());
+            let step = eval_tail(body, name, state);
+            state.scopes.pop();
+            match step {
+                Ok(Tail::Call(next)) if tail_calls < max_tail_calls => {
+                    check_arity(name, params.len(), next.len())?;
+                    args = next;
+                    tail_calls += 1;
+                }
+                Ok(Tail::Call(_)) => break Err("maximum recursion depth exceeded".into()),
+                Ok(Tail::Done(value)) => break Ok(value),
+                Err(e) => break Err(e),
+            }
+        };
+        if let (Some(key), Ok(value)) = (key, &result) {
+            state.memo.insert(key, value.clone());
+        }
+        return result;
+    }
+    if let Some(native) = state.funcs_native.get(name) {
+        let reals = values.iter().map(Value::as_real).collect::<Result<Vec<_>, _>>()?;
+        return Ok(Value::Real(native(&reals)?));
+    }
+    if !BUILTIN_FUNCTIONS.contains(&name) {
+        return Err(unknown_function(name, &Span::default(), state));
+    }
+    if name == "rand" {
+        return random(values, state);
+    }
+    let result = call_builtin(name, values, state)?;
+    if values.iter().any(|value| matches!(value, Value::Decimal(..))) && matches!(result, Value::Real(_)) {
+        warn_float_fallback(name);
+    }
+    Ok(result)
+}
+
+// Tells the user that a result in decimal mode isn't exact: a built-in with
+// no decimal version, or an operation whose exact result overflows or
+// doesn't terminate.
+fn warn_float_fallback(what: &str) {
+    eprintln!("Warning: {} is computed in floating point", what);
+}
+
+// Fills each `{}` in `template` with the next argument as it would print,
+// and each `{:.N}` with a real rounded to N decimal places. `{{` and `}}`
+// stand for literal braces.
+fn format_template(template: &str, args: &[Value]) -> Result<String, EvalError> {
+    let mut out = String::new();
+    let mut args_left = args.iter();
+    let mut placeholders = 0;
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        out.push_str(&rest[..i]);
+        let tail = &rest[i..];
+        if tail.starts_with("{{") || tail.starts_with("}}") {
+            out.push_str(&tail[..1]);
+            rest = &tail[2..];
+            continue;
+        }
+        if tail.starts_with('}') {
+            return Err("format: unmatched '}' in template".into());
+        }
+        let end = tail.find('}').ok_or("format: unclosed '{' in template")?;
+        let spec = &tail[1..end];
+        placeholders += 1;
+        if let Some(arg) = args_left.next() {
+            match spec {
+                "" => out.push_str(&arg.to_string()),
+                _ if let Some(digits) = spec.strip_prefix(":.") && let Ok(digits) = digits.parse::<usize>() => {
+                    if digits > MAX_PRECISION {
+                        return Err(format!("format: precision {} is more than the limit of {}", digits, MAX_PRECISION).into());
+                    }
+                    out.push_str(&format!("{:.*}", digits, arg.as_real()?));
+                }
+                _ => return Err(format!("format: unsupported placeholder '{{{}}}'", spec).into()),
+            }
+        }
+        rest = &tail[end + 1..];
+    }
+    out.push_str(rest);
+    if placeholders != args.len() {
+        return Err(format!("format: template has {} placeholder(s) but got {} argument(s)", placeholders, args.len()).into());
+    }
+    Ok(out)
+}
+
+// rand() is uniform on [0, 1) and rand(lo, hi) on [lo, hi).
+fn random(values: &[Value], state: &mut State) -> Result<Value, EvalError> {
+    let (lo, hi) = match values {
+        [] => (0.0, 1.0),
+        [lo, hi] => (lo.as_real()?, hi.as_real()?),
+        _ => return Err(format!("rand expects 0 or 2 arguments, got {}", values.len()).into()),
+    };
+    if lo > hi || !(hi - lo).is_finite() {
+        return Err(EvalError::DomainError { func: "rand".to_string(), message: format!("rand needs finite bounds with lo <= hi, got {} and {}", lo, hi) });
+    }
+    Ok(Value::Real(lo + (hi - lo) * state.next_random()))
+}
+
+// The candidate closest to `name`, or None when nothing is close enough. Short names allow fewer edits, and ties go to the
+// candidate sharing the longest prefix with `name`.
+fn did_you_mean<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Option<String> {
+    let max_distance = (name.len() / 3).clamp(1, MAX_SUGGESTION_DISTANCE);
+    let closest = candidates
+        .filter(|candidate| *candidate != name)
+        .map(|candidate| {
+            let prefix = name.chars().zip(candidate.chars()).take_while(|(a, b)| a == b).count();
+            (edit_distance(name, candidate), std::cmp::Reverse(prefix), candidate)
+        })
+        .filter(|(distance, _, _)| *distance <= max_distance && *distance < name.len())
+        .min();
+    closest.map(|(_, _, candidate)| candidate.to_string())
+}
+
+// Levenshtein distance between two names.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn call_lambda(name: &str, lambda: &value::Lambda, values: &[Value], state: &mut State) -> Result<Value, EvalError> {
+    check_arity(name, lambda.params.len(), values.len())?;
+    if state.scopes.len() >= state.max_call_depth {
+        return Err("maximum recursion depth exceeded".into());
+    }
+    let mut frame = lambda.captured.clone();
+    frame.extend(lambda.params.iter().cloned().zip(values.iter().cloned()));
+    state.scopes.push(frame);
+    let result = eval(&lambda.body, state);
+    state.scopes.pop();
+    result
+}
+
+// Calls the function given as an argument to a higher-order form: either a
+// function name or an expression producing a lambda.
+fn apply(func: &Expr, values: &[Value], state: &mut State) -> Result<Value, EvalError> {
+    if let Expr::Variable(name, _) = func {
+        return call_function(name, values, state);
+    }
+    match eval(func, state)? {
+        Value::Lambda(lambda) => call_lambda("<lambda>", &lambda, values, state),
+        Value::Function(name) => call_named(&name, values, state),
+        other => Err(format!("Expected a function, got {}", other).into()),
+    }
+}
+
+// Calls a one-argument function that must produce a real number, as needed by
+// the numeric calculus forms.
+fn call_real(name: &str, x: f64, state: &mut State) -> Result<f64, EvalError> {
+    call_function(name, &[Value::Real(x)], state)?.as_real()
+}
+
+fn call_reals(name: &str, xs: &[f64], state: &mut State) -> Result<f64, EvalError> {
+    call_function(name, &xs.iter().map(|x| Value::Real(*x)).collect::<Vec<_>>(), state)?.as_real()
+}
+
+// Built-ins that accept complex arguments; everything else takes reals only.
+fn call_builtin(name: &str, values: &[Value], state: &State) -> Result<Value, EvalError> {
+    match (name, values) {
+        // outside the matrix functions a matrix is the list of its rows
+        _ if !matches!(name, "transpose" | "det" | "inv") && values.iter().any(|value| matches!(value, Value::Matrix(_))) => {
+            let values: Vec<Value> = values
+                .iter()
+                .map(|value| match value {
+                    Value::Matrix(m) => Value::List(matrix::to_rows(m)),
+                    value => value.clone(),
+                })
+                .collect();
+            Ok(match call_builtin(name, &values, state)? {
+                Value::List(items) => matrix::list_or_matrix(items),
+                value => value,
+            })
+        }
+        ("sum", [Value::List(items)]) => match items.split_first() {
+            Some((first, rest)) => rest.iter().try_fold(first.clone(), |acc, x| binary_op(&acc, "+", x, false)),
+            None => Ok(Value::Real(0.0)),
+        },
+        ("sum", _) => Err("sum expects a list, or a function name and integer bounds, e.g. sum(f, 1, 10)".into()),
+        ("mean", [Value::List(items)]) if items.is_empty() => Err("mean of an empty list".into()),
+        ("mean", [Value::List(items)]) => {
+            let total = call_builtin("sum", values, state)?;
+            // an integer count keeps the mean of exact values exact
+            binary_op(&total, "/", &Value::Rational(items.len() as i64, 1), false)
+        }
+        ("stddev", [Value::List(items)]) if items.len() < 2 => {
+            Err(format!("stddev needs at least 2 elements, got {}", items.len()).into())
+        }
+        ("stddev", [Value::List(items)]) => {
+            let xs = items.iter().map(Value::as_real).collect::<Result<Vec<_>, _>>()?;
+            let mean = xs.iter().sum::<f64>() / xs.len() as f64;
+            let variance = xs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (xs.len() - 1) as f64;
+            Ok(Value::Real(variance.sqrt()))
+        }
+        ("max" | "min", [Value::List(items)]) => call_builtin(name, items, state),
+        ("range", [start, stop]) => call_builtin(name, &[start.clone(), stop.clone(), Value::Real(1.0)], state),
+        ("range", [start, stop, step]) => {
+            let (start, stop, step) = (start.as_real()?, stop.as_real()?, step.as_real()?);
+            if step == 0.0 {
+                return Err("range step cannot be zero".into());
+            }
+            // computing each element from its index avoids accumulating rounding error
+            let count = ((stop - start) / step).ceil().max(0.0);
+            if !count.is_finite() {
+                return Err("range bounds must be finite".into());
+            }
+            if count > state.max_loop_iterations as f64 {
+                return Err(format!("range would have {} elements, more than the limit of {}", count, state.max_loop_iterations).into());
+            }
+            Ok(Value::List((0..count as usize).map(|k| Value::Real(start + k as f64 * step)).collect()))
+        }
+        ("range", _) => Err(format!("range expects 2 or 3 arguments, got {}", values.len()).into()),
+        ("dot" | "cross", [Value::List(a), Value::List(b)]) if a.len() != b.len() => {
+            Err(format!("{} needs lists of equal length, got {} and {}", name, a.len(), b.len()).into())
+        }
+        ("dot", [Value::List(a), Value::List(b)]) => a
+            .iter()
+            .zip(b)
+            .try_fold(Value::Real(0.0), |acc, (x, y)| binary_op(&acc, "+", &binary_op(x, "*", y, false)?, false)),
+        ("cross", [Value::List(a), Value::List(_)]) if a.len() != 3 => {
+            Err(format!("cross needs lists of length 3, got length {}", a.len()).into())
+        }
+        ("cross", [Value::List(a), Value::List(b)]) => {
+            let term = |i: usize, j: usize| -> Result<Value, EvalError> {
+                binary_op(&binary_op(&a[i], "*", &b[j], false)?, "-", &binary_op(&a[j], "*", &b[i], false)?, false)
+            };
+            Ok(Value::List(vec![term(1, 2)?, term(2, 0)?, term(0, 1)?]))
+        }
+        ("norm", [v @ Value::List(_)]) => call_builtin(name, &[v.clone(), Value::Real(2.0)], state),
+        ("norm", [Value::List(items), _]) if items.is_empty() => Err("norm of an empty list".into()),
+        ("norm", [Value::List(items), p]) => {
+            let p = p.as_real()?;
+            if p.is_nan() || p < 1.0 {
+                return Err(EvalError::DomainError { func: name.to_string(), message: format!("norm order must be at least 1, got {}", p) });
+            }
+            let xs = items.iter().map(|x| x.as_real().map(f64::abs)).collect::<Result<Vec<_>, _>>()?;
+            let largest = xs.iter().copied().fold(0.0, f64::max);
+            if p == f64::INFINITY || largest == 0.0 || !largest.is_finite() {
+                return Ok(Value::Real(largest));
+            }
+            // dividing by the largest element keeps the powers from overflowing
+            // or underflowing
+            let total: f64 = xs.iter().map(|x| (x / largest).powf(p)).sum();
+            Ok(Value::Real(largest * total.powf(1.0 / p)))
+        }
+        ("norm", _) => Err("norm expects a list and an optional order, e.g. norm([3, 4]) or norm(v, inf)".into()),
+        ("dot" | "cross", _) => Err(format!("{} expects two lists, e.g. {}([1, 2, 3], [4, 5, 6])", name, name).into()),
+        ("transpose", [Value::Matrix(m)]) => Ok(Value::Matrix(matrix::transpose(m))),
+        ("det", [Value::Matrix(m)]) => matrix::determinant(m).map(Value::Real),
+        ("inv", [Value::Matrix(m)]) => matrix::inverse(m).map(Value::Matrix),
+        ("transpose" | "det" | "inv", _) => Err(format!("{} expects a matrix, e.g. {}([[1, 2], [3, 4]])", name, name).into()),
+        ("assert", [cond]) if cond.is_truthy() => Ok(Value::Real(1.0)),
+        ("assert", [cond, Value::Str(_)]) if cond.is_truthy() => Ok(Value::Real(1.0)),
+        ("assert", [_]) => Err(EvalError::AssertionFailed(None)),
+        ("assert", [_, Value::Str(message)]) => Err(EvalError::AssertionFailed(Some(message.clone()))),
+        ("assert", _) => Err("assert expects a condition and an optional message, e.g. assert(x > 0, \"x must be positive\")".into()),
+        ("format", [Value::Str(template), args @ ..]) => format_template(template, args).map(Value::Str),
+        ("format", _) => Err("format expects a template string first, e.g. format(\"{:.2}\", x)".into()),
+        // one-argument built-ins map over lists
+        (_, [Value::List(items)]) => items
+            .iter()
+            .map(|item| call_builtin(name, std::slice::from_ref(item), state))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        // integers as text in another base, e.g. hex(255) is 0xff
+        ("hex" | "bin" | "oct", [x]) => {
+            let n = as_integer(name, x.as_real()?)?;
+            let digits = match name {
+                "hex" => format!("{:#x}", n.unsigned_abs()),
+                "bin" => format!("{:#b}", n.unsigned_abs()),
+                _ => format!("{:#o}", n.unsigned_abs()),
+            };
+            Ok(Value::Str(if n < 0 { format!("-{}", digits) } else { digits }))
+        }
+        ("re", [z]) => Ok(Value::Real(z.parts().0)),
+        ("im", [z]) => Ok(Value::Real(z.parts().1)),
+        ("conj", [z]) => Ok(Value::complex(z.parts().0, -z.parts().1)),
+        ("arg", [z]) => Ok(Value::Real(z.parts().1.atan2(z.parts().0))),
+        ("abs", [Value::Quantity(x, dims)]) => Ok(Value::Quantity(x.abs(), *dims)),
+        ("sqrt", [q @ Value::Quantity(..)]) => quantity_op(q, "^", &Value::Real(0.5), false),
+        ("abs", [Value::Rational(num, den)]) if *num != i64::MIN => Ok(Value::Rational(num.abs(), *den)),
+        ("abs", [z]) => Ok(Value::Real(z.parts().0.hypot(z.parts().1))),
+        ("sqrt", [Value::Rational(num, den)]) if let Some(root) = value::rational_sqrt((*num, *den)) => Ok(root),
+        ("sqrt", [z]) if z.parts().0 >= 0.0 && z.parts().1 == 0.0 => Ok(Value::Real(z.parts().0.sqrt())),
+        ("sqrt", [z]) => {
+            let (re, im) = value::complex_sqrt(z.parts());
+            Ok(Value::complex(re, im))
+        }
+        ("exp", [z @ Value::Complex(..)]) => {
+            let (re, im) = value::complex_exp(z.parts());
+            Ok(Value::complex(re, im))
+        }
+        ("ln", [z @ Value::Complex(..)]) => {
+            let (re, im) = value::complex_ln(z.parts());
+            Ok(Value::complex(re, im))
+        }
+        _ => {
+            if let Some(expected) = builtin_arity(name) {
+                check_arity(name, expected, values.len())?;
+            }
+            let reals = values.iter().map(Value::as_real).collect::<Result<Vec<_>, _>>()?;
+            call_real_builtin(name, &reals, state.angle_mode).map(Value::Real)
+        }
+    }
+}
+
+// How many arguments a built-in takes, for those that take a fixed number.
+fn builtin_arity(name: &str) -> Option<usize> {
+    match name {
+        "sin" | "cos" | "tan" | "asin" | "acos" | "atan" | "sinh" | "cosh" | "tanh" | "asinh" | "acosh" | "atanh" | "ln"
+        | "log10" | "log2" | "exp" | "sqrt" | "cbrt" | "floor" | "ceil" | "round" | "trunc" | "sign" | "abs" | "fact"
+        | "gamma" | "not" | "erf" | "erfc" | "deg2rad" | "rad2deg" | "re" | "im" | "conj" | "arg" | "hex" | "bin" | "oct" => {
+            Some(1)
+        }
+        "atan2" | "log" | "gcd" | "lcm" | "band" | "bor" | "bxor" | "shl" | "shr" | "ncr" | "npr" | "hypot" | "mod" => Some(2),
+        "clamp" | "lerp" => Some(3),
+        _ => None,
+    }
+}
+
+fn domain_error(func: &str, message: String) -> EvalError {
+    EvalError::DomainError { func: func.to_string(), message }
+}
+
+fn call_real_builtin(name: &str, values: &[f64], angle_mode: AngleMode) -> Result<f64, EvalError> {
+    let degrees = angle_mode == AngleMode::Degrees;
+    match (name, values) {
+        ("sin", [x]) if degrees => Ok(dmath::dsin(*x)),
+        ("sin", [x]) => Ok(x.sin()),
+        ("cos", [x]) if degrees => Ok(dmath::dcos(*x)),
+        ("cos", [x]) => Ok(x.cos()),
+        ("tan", [x]) if degrees => Ok(dmath::dtan(*x)),
+        ("tan", [x]) => Ok(x.tan()),
+        ("asin", [x]) if !(-1.0..=1.0).contains(x) => Err(domain_error(name, format!("asin domain error: {}", x))),
+        ("asin", [x]) if degrees => Ok(dmath::dasin(*x)),
+        ("asin", [x]) => Ok(x.asin()),
+        ("acos", [x]) if !(-1.0..=1.0).contains(x) => Err(domain_error(name, format!("acos domain error: {}", x))),
+        ("acos", [x]) if degrees => Ok(dmath::dacos(*x)),
+        ("acos", [x]) => Ok(x.acos()),
+        ("atan", [x]) if degrees => Ok(dmath::datan(*x)),
+        ("atan", [x]) => Ok(x.atan()),
+        ("atan2", [y, x]) if degrees => Ok(dmath::datan2(*y, *x)),
+        ("atan2", [y, x]) => Ok(y.atan2(*x)),
+        ("sinh", [x]) => Ok(x.sinh()),
+        ("cosh", [x]) => Ok(x.cosh()),
+        ("tanh", [x]) => Ok(x.tanh()),
+        ("asinh", [x]) => Ok(x.asinh()),
+        ("acosh", [x]) if *x < 1.0 => Err(domain_error(name, format!("acosh domain error: {} (must be >= 1)", x))),
+        ("acosh", [x]) => Ok(x.acosh()),
+        ("atanh", [x]) if x.abs() >= 1.0 => Err(domain_error(name, format!("atanh domain error: {} (must satisfy |x| < 1)", x))),
+        ("atanh", [x]) => Ok(x.atanh()),
+        ("ln" | "log10" | "log2", [x]) if *x <= 0.0 => Err(domain_error(name, format!("log of non-positive number: {}", x))),
+        ("ln", [x]) => Ok(x.ln()),
+        ("log10", [x]) => Ok(x.log10()),
+        ("log2", [x]) => Ok(x.log2()),
+        ("log", [base, x]) if *base <= 0.0 || *x <= 0.0 => {
+            Err(domain_error(name, format!("log of non-positive number: {}", if *x <= 0.0 { x } else { base })))
+        }
+        ("log", [base, _]) if *base == 1.0 => Err(domain_error(name, "log base cannot be 1".to_string())),
+        ("log", [base, x]) => Ok(x.log(*base)),
+        ("exp", [x]) => Ok(x.exp()),
+        ("cbrt", [x]) => Ok(x.cbrt()),
+        ("floor", [x]) => Ok(x.floor()),
+        ("ceil", [x]) => Ok(x.ceil()),
+        // rounds half away from zero: round(2.5) = 3, round(-2.5) = -3
+        ("round", [x]) => Ok(x.round()),
+        ("trunc", [x]) => Ok(x.trunc()),
+        ("sign", [x]) if *x == 0.0 => Ok(0.0),
+        ("sign", [x]) => Ok(x.signum()),
+        ("max" | "min", []) => Err(format!("{} requires at least one argument", name).into()),
+        ("max", [first, rest @ ..]) => Ok(rest.iter().fold(*first, |acc, v| acc.max(*v))),
+        ("min", [first, rest @ ..]) => Ok(rest.iter().fold(*first, |acc, v| acc.min(*v))),
+        ("gcd", [a, b]) => Ok(gcd(as_integer("gcd", *a)?, as_integer("gcd", *b)?) as f64),
+        ("band" | "bor" | "bxor", [a, b]) => {
+            let (a, b) = (as_integer(name, *a)?, as_integer(name, *b)?);
+            Ok(match name {
+                "band" => a & b,
+                "bor" => a | b,
+                _ => a ^ b,
+            } as f64)
+        }
+        ("shl" | "shr", [a, n]) => {
+            let (a, n) = (as_integer(name, *a)?, as_integer(name, *n)?);
+            let shift = u32::try_from(n).ok().filter(|n| *n < 64).ok_or_else(|| format!("{} shift must be between 0 and 63, got {}", name, n))?;
+            Ok(if name == "shl" { a << shift } else { a >> shift } as f64)
+        }
+        ("lcm", [a, b]) => {
+            let (a, b) = (as_integer("lcm", *a)?, as_integer("lcm", *b)?);
+            if a == 0 || b == 0 {
+                return Ok(0.0);
+            }
+            (a.unsigned_abs() / gcd(a, b))
+                .checked_mul(b.unsigned_abs())
+                .map(|v| v as f64)
+                .ok_or_else(|| "lcm overflow".into())
+        }
+        ("fact", [n]) => factorial(*n),
+        ("gamma", [x]) if *x <= 0.0 && x.fract() == 0.0 => Err(domain_error(name, format!("gamma undefined at {}", x))),
+        ("gamma", [x]) => Ok(gamma(*x)),
+        ("ncr", [n, k]) => combinations(*n, *k),
+        ("npr", [n, k]) => permutations(*n, *k),
+        ("clamp", [_, lo, hi]) if lo > hi => Err("clamp: lo > hi".into()),
+        ("clamp", [_, lo, hi]) if lo.is_nan() || hi.is_nan() => Err("clamp: bounds must not be nan".into()),
+        ("clamp", [x, lo, hi]) => Ok(x.clamp(*lo, *hi)),
+        ("lerp", [a, b, t]) => Ok(a + (b - a) * t),
+        ("hypot", [x, y]) => Ok(x.hypot(*y)),
+        ("mod", [a, b]) => euclid_mod(*a, *b),
+        ("not", [x]) => Ok(bool_to_f64(*x == 0.0)),
+        ("erf", [x]) => Ok(erf(*x)),
+        ("erfc", [x]) => Ok(erfc(*x)),
+        // the standard normal unless a mean and standard deviation are given
+        ("normpdf" | "normcdf", [x]) => call_real_builtin(name, &[*x, 0.0, 1.0], angle_mode),
+        ("normpdf" | "normcdf", [_, _, sigma]) if *sigma <= 0.0 || sigma.is_nan() => Err(EvalError::DomainError {
+            func: name.to_string(),
+            message: format!("{} needs a positive standard deviation, got {}", name, sigma),
+        }),
+        ("normpdf", [x, mu, sigma]) => {
+            let z = (x - mu) / sigma;
+            Ok((-0.5 * z * z).exp() / (sigma * (2.0 * std::f64::consts::PI).sqrt()))
+        }
+        ("normcdf", [x, mu, sigma]) => Ok(0.5 * erfc(-(x - mu) / (sigma * std::f64::consts::SQRT_2))),
+        ("normpdf" | "normcdf", _) => Err(format!("{} expects 1 or 3 arguments, got {}", name, values.len()).into()),
+        ("deg2rad", [x]) => Ok(x.to_radians()),
+        ("rad2deg", [x]) => Ok(x.to_degrees()),
+        _ => Err(EvalError::UnknownFunction { name: name.to_string(), suggestion: None, span: None }),
+    }
+}
+
+// Comparisons treat values within EPSILON of each other as equal, scaled by
+// magnitude once either side exceeds 1, so `0.1 + 0.2 == 0.3` holds. 1e-9 is
+// far above f64 rounding noise (~1e-16) but well below any difference a user
+// would type on purpose.
+const EPSILON: f64 = 1e-9;
+
+fn approx_eq(a: f64, b: f64) -> bool {
+    a == b || (a - b).abs() <= EPSILON * a.abs().max(b.abs()).max(1.0)
+}
+
+fn bool_to_f64(b: bool) -> f64 {
+    if b { 1.0 } else { 0.0 }
+}
+
+fn euclid_mod(a: f64, b: f64) -> Result<f64, EvalError> {
+    if b == 0.0 {
+        return Err(EvalError::DivByZero);
+    }
+    Ok(a.rem_euclid(b))
+}
+
+fn as_integer(func: &str, x: f64) -> Result<i64, EvalError> {
+    if x.fract() != 0.0 || !x.is_finite() {
+        return Err(EvalError::DomainError {
+            func: func.to_string(),
+            message: format!("{} expects integer arguments, got {}", func, x),
+        });
+    }
+    // -2^63 is exact as an f64 but i64::MAX rounds up to 2^63, which is out
+    // of range
+    if !(i64::MIN as f64..-(i64::MIN as f64)).contains(&x) {
+        return Err(EvalError::DomainError {
+            func: func.to_string(),
+            message: format!("{} argument is out of the 64-bit integer range: {}", func, x),
+        });
+    }
+    Ok(x as i64)
+}
+
+// unsigned so that gcd(-2^63, 0) = 2^63 fits
+fn gcd(a: i64, b: i64) -> u64 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn factorial(n: f64) -> Result<f64, EvalError> {
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(EvalError::DomainError {
+            func: "factorial".to_string(),
+            message: format!("factorial expects a non-negative integer, got {}", n),
+        });
+    }
+    if n > 170.0 {
+        return Err(format!("factorial overflow: {}! is too large", n).into());
+    }
+    let mut result = 1.0;
+    for k in 2..=n as u32 {
+        result *= k as f64;
+    }
+    Ok(result)
+}
+
+fn check_n_k(func: &str, n: f64, k: f64) -> Result<(u64, u64), EvalError> {
+    let (n, k) = (as_integer(func, n)?, as_integer(func, k)?);
+    if n < 0 || k < 0 {
+        return Err(EvalError::DomainError { func: func.to_string(), message: format!("{} expects non-negative arguments", func) });
+    }
+    if k > n {
+        return Err(EvalError::DomainError { func: func.to_string(), message: format!("{}: k ({}) is greater than n ({})", func, k, n) });
+    }
+    Ok((n as u64, k as u64))
+}
+
+// n! / (n - k)!, multiplying only the factors that survive the cancellation
+fn permutations(n: f64, k: f64) -> Result<f64, EvalError> {
+    let (n, k) = check_n_k("npr", n, k)?;
+    Ok(((n - k + 1)..=n).fold(1.0, |acc, i| acc * i as f64))
+}
+
+// n! / (k! (n - k)!), built up term by term so intermediates stay small
+fn combinations(n: f64, k: f64) -> Result<f64, EvalError> {
+    let (n, k) = check_n_k("ncr", n, k)?;
+    let k = k.min(n - k);
+    Ok((1..=k).fold(1.0, |acc, i| acc * (n - k + i) as f64 / i as f64).round())
+}
+
+// Lanczos approximation (g = 7, n = 9), with the reflection formula below 0.5
+fn gamma(x: f64) -> f64 {
+    const G: f64 = 7.0;
+    const COEFFS: [f64; 9] = [
+        0.999_999_999_999_809_9,
+        676.520_368_121_885_1,
+        -1_259.139_216_722_402_8,
+        771.323_428_777_653_1,
+        -176.615_029_162_140_6,
+        12.507_343_278_686_905,
+        -0.138_571_095_265_720_12,
+        9.984_369_578_019_572e-6,
+        1.505_632_735_149_311_6e-7,
+    ];
+    if x < 0.5 {
+        return std::f64::consts::PI / ((std::f64::consts::PI * x).sin() * gamma(1.0 - x));
+    }
+    let x = x - 1.0;
+    let mut a = COEFFS[0];
+    let t = x + G + 0.5;
+    for (i, c) in COEFFS.iter().enumerate().skip(1) {
+        a += c / (x + i as f64);
+    }
+    (2.0 * std::f64::consts::PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * a
+}
+
+// Below ERF_SERIES_LIMIT erf is summed from its series, whose terms are all
+// positive so nothing cancels; above it erfc comes from its continued
+// fraction, which converges quickly there and keeps the tail's relative
+// accuracy.
+const ERF_SERIES_LIMIT: f64 = 2.5;
+
+fn erf(x: f64) -> f64 {
+    if x.is_nan() {
+        return x;
+    }
+    let a = x.abs();
+    let magnitude = if a < ERF_SERIES_LIMIT {
+        // erf(a) = 2/sqrt(pi) * exp(-a^2) * sum 2^n a^(2n+1) / (1 * 3 * ... * (2n+1))
+        let (mut term, mut total, mut n) = (a, a, 0.0);
+        while term > total * f64::EPSILON {
+            n += 1.0;
+            term *= 2.0 * a * a / (2.0 * n + 1.0);
+            total += term;
+        }
+        2.0 / std::f64::consts::PI.sqrt() * (-a * a).exp() * total
+    } else {
+        1.0 - erfc(a)
+    };
+    magnitude.copysign(x)
+}
+
+fn erfc(x: f64) -> f64 {
+    if x < ERF_SERIES_LIMIT {
+        return 1.0 - erf(x);
+    }
+    // erfc(x) = exp(-x^2) / sqrt(pi) / (x + (1/2) / (x + (2/2) / (x + (3/2) / ...)))
+    let fraction = (1..=60).rev().fold(x, |tail, n| x + n as f64 / 2.0 / tail);
+    (-x * x).exp() / (std::f64::consts::PI.sqrt() * fraction)
+}
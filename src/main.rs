@@ -1,225 +1,435 @@
-mod differential;
-mod dmath;
-
-use std::collections::HashMap;
-use std::io::{self, Write};
+use std::env;
+use std::io::{self, IsTerminal, Write};
 use std::fs;
+use std::process;
+use std::thread;
 
-use pest::Parser;
-use pest_derive::Parser;
-// use crate::Stmt::Expr;
-
-struct State {
-    vars: HashMap<String, f64>,
-    funcs: HashMap<String, (String, Expr)>,
-}
-
-#[derive(Debug, Clone)]
-pub enum Stmt {
-    FuncDef(String, String, Expr),
-    Assign(String, Expr),
-    Print(Expr),
-    Expr(Expr),
-}
+use function::{AngleMode, EvalError, Expr, MAX_PRECISION, NumberFormat, State, ast_tree, eval_program, is_incomplete, parse, parse_program, token_tree};
 
+const INTERPRETER_STACK_SIZE: usize = 256 * 1024 * 1024;
+// what evaluation may use of it, leaving room for the frames around it
+const INTERPRETER_STACK_BUDGET: usize = INTERPRETER_STACK_SIZE - 16 * 1024 * 1024;
 
-#[derive(Parser)]
-#[grammar = "math.pest"]
-struct MathParser;
-
-#[derive(Debug, Clone)]
-enum Expr {
-    Number(f64),
-    Variable(String),
-    UnaryOp { op: String, expr: Box<Expr> },
-    BinaryOp { left: Box<Expr>, op: String, right: Box<Expr> },
-    FunctionCall { name: String, args: Vec<Expr> },
-    Assignment { name: String, value: Box<Expr> },
-    FunctionDef { name: String, param: String, body: Box<Expr> },
-    Print(Box<Expr>),
+fn new_state() -> State {
+    let mut state = State::new();
+    state.max_stack_bytes = INTERPRETER_STACK_BUDGET;
+    state
 }
 
-fn parse_expr(pair: pest::iterators::Pair<Rule>) -> Expr {
-    match pair.as_rule() {
-        Rule::number => Expr::Number(pair.as_str().parse().expect("...")),
-        Rule::ident => Expr::Variable(pair.as_str().to_string()),
-        Rule::function_call => {
-            let mut inner = pair.into_inner();
-            let name = inner.next().expect("...").as_str().to_string();
-            let args = inner.map(parse_expr).collect();
-            Expr::FunctionCall { name, args }
-        }
-        Rule::unary => {
-            let mut inner = pair.into_inner();
-            let first = inner.next().expect("...");
-            if first.as_rule() == Rule::primary {
-                parse_expr(first)
-            } else {
-                let op = first.as_str().to_string();
-                let expr = parse_expr(inner.next().expect("..."));
-                Expr::UnaryOp { op, expr: Box::new(expr) }
+// Replaces the body of every /* */ comment with spaces, keeping newlines so
+// that a comment spanning several lines doesn't shift the line numbers.
+// String literals and # comments are copied unchanged, so a `/*` inside
+// them doesn't start a comment.
+fn blank_block_comments(source: &str) -> String {
+    let mut out = String::with_capacity(source.len());
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        out.push(c);
+        match c {
+            // a string ends at its closing quote, or at the end of the line if unclosed
+            '"' => {
+                while let Some(c) = chars.next() {
+                    out.push(c);
+                    match c {
+                        '\\' => out.extend(chars.next_if(|c| *c != '\n')),
+                        '"' | '\n' => break,
+                        _ => {}
+                    }
+                }
             }
-        }
-        Rule::power | Rule::product | Rule::sum => {
-            println!("{}", pair);
-            let mut inner = pair.into_inner();
-            let mut expr = parse_expr(inner.next().expect("..."));
-            while let Some(op) = inner.next() {
-                let right = parse_expr(inner.next().expect("Expected right-hand expression"));
-                expr = Expr::BinaryOp {
-                    left: Box::new(expr),
-                    op: op.as_str().to_string(),
-                    right: Box::new(right),
-                };
+            '#' => {
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    out.push(c);
+                }
+            }
+            '/' if chars.next_if_eq(&'*').is_some() => {
+                out.pop();
+                out.push_str("  ");
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    out.push(if c == '\n' { '\n' } else { ' ' });
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
             }
-            expr
+            _ => {}
         }
-        Rule::assignment => {
-            let mut inner = pair.into_inner();
-            let name = inner.next().expect("...").as_str().to_string();
-            let value = parse_expr(inner.next().expect("..."));
-            Expr::Assignment { name, value: Box::new(value) }
+    }
+    out
+}
+
+// Splits a script into statements, returning each with its first and last
+// line index. A line that ends mid-statement (say, after an operator or
+// inside brackets) continues onto the following lines.
+fn statements(contents: &str) -> Vec<(usize, usize, String)> {
+    let lines: Vec<&str> = contents.lines().collect();
+    let mut statements = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].trim().is_empty() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        let mut statement = lines[i].to_string();
+        while is_incomplete(&statement) && i + 1 < lines.len() {
+            i += 1;
+            statement.push('\n');
+            statement.push_str(lines[i]);
         }
-        Rule::function_def => {
-            let mut inner = pair.into_inner();
-            let name = inner.next().expect("Expected function name").as_str().to_string();
-            let param = inner.next().expect("Expected function parameter").as_str().to_string();
-            let body = parse_expr(inner.next().expect("Expected function body"));
-            Ok(Expr::FunctionDef(name, param, body))
+        statements.push((start, i, statement));
+        i += 1;
+    }
+    statements
+}
+
+fn location(start: usize, end: usize) -> String {
+    if start == end { format!("Line {}", start + 1) } else { format!("Lines {}-{}", start + 1, end + 1) }
+}
+
+// Leading newlines are whitespace to the parser but make the line numbers in
+// parse errors match the file.
+fn parse_statement(start: usize, statement: &str) -> Result<Vec<Expr>, EvalError> {
+    parse_program(&("\n".repeat(start) + statement))
+}
+
+// Runs a script, printing each result. Returns false if the file couldn't be
+// read or an assertion failed, either of which stops the run.
+fn run_file(filename: &str, state: &mut State) -> bool {
+    let contents = match fs::read_to_string(filename) {
+        Ok(contents) => blank_block_comments(&contents),
+        Err(e) => {
+            println!("Could not read file {}: {}", filename, e);
+            return false;
         }
-        Rule::print_stmt => {
-            let inner = pair.into_inner().next().expect("...");
-            Expr::Print(Box::new(parse_expr(inner)))
+    };
+    for (start, end, statement) in statements(&contents) {
+        let location = location(start, end);
+        let text = statement.split_whitespace().collect::<Vec<_>>().join(" ");
+        match parse_statement(start, &statement) {
+            Ok(exprs) => match eval_program(&exprs, state) {
+                Ok(Some(result)) => println!("{}: {} = {}", location, text, state.format_value(&result)),
+                Ok(None) => {}
+                // a failed assertion means the rest of the script can't be trusted
+                Err(e @ EvalError::AssertionFailed(_)) => {
+                    println!("{}: {}", location, e);
+                    return false;
+                }
+                Err(e) => println!("{}: Error evaluating '{}': {}", location, text, e),
+            },
+            Err(e) => println!("{}: Parse error: {}", location, e),
         }
-        Rule::expression | Rule::statement => parse_expr(pair.into_inner().next().expect("...")),
-        Rule::primary => parse_expr(pair.into_inner().next().expect("...")),
-        _ => unreachable!("Unexpected rule: {:?}", pair.as_rule()),
     }
+    true
+}
+
+// Evaluates a script without printing results, stopping at the first error.
+fn load_file(filename: &str, state: &mut State) -> Result<(), String> {
+    let contents = fs::read_to_string(filename).map_err(|e| format!("Could not read file {}: {}", filename, e))?;
+    for (start, end, statement) in statements(&blank_block_comments(&contents)) {
+        parse_statement(start, &statement)
+            .and_then(|exprs| eval_program(&exprs, state))
+            .map_err(|e| format!("{}: {}", location(start, end), e))?;
+    }
+    Ok(())
 }
 
-fn eval(expr: Expr, state: &mut State) -> Result<f64, String> {
-    match expr {
-        Expr::Number(n) => Ok(n),
-        Expr::Variable(name) => match name.as_str() {
-            "pi" => Ok(std::f64::consts::PI),
-            "e" => Ok(std::f64::consts::E),
-            _ => state.vars.get(&name).copied().ok_or_else(|| format!("Undefined variable: {}", name)),
+// Handles a `:command` REPL line, returning the text to show the user.
+fn run_command(line: &str, state: &mut State) -> Result<String, String> {
+    let mut parts = line.splitn(2, char::is_whitespace);
+    let command = parts.next().unwrap_or_default();
+    let arg = parts.next().unwrap_or_default().trim();
+    match command {
+        ":mode" => match arg {
+            "deg" => state.angle_mode = AngleMode::Degrees,
+            "rad" => state.angle_mode = AngleMode::Radians,
+            other => return Err(format!("Unknown angle mode: {} (expected 'deg' or 'rad')", other)),
         },
-        Expr::UnaryOp { op, expr } => {
-            let val = eval(*expr, state)?;
-            match op.as_str() {
-                "+" => Ok(val),
-                "-" => Ok(-val),
-                _ => Err(format!("Unknown unary operator: {}", op)),
-            }
+        ":precision" if arg.is_empty() => state.precision = None,
+        ":precision" => {
+            let digits = arg.parse().ok().filter(|digits| *digits <= MAX_PRECISION);
+            state.precision = Some(digits.ok_or_else(|| format!("Invalid precision: {} (expected 0 to {})", arg, MAX_PRECISION))?);
         }
-        Expr::BinaryOp { left, op, right } => {
-            let l = eval(*left, state)?;
-            let r = eval(*right, state)?;
-            match op.as_str() {
-                "+" => Ok(l + r),
-                "-" => Ok(l - r),
-                "*" => Ok(l * r),
-                "/" => Ok(l / r),
-                "^" => Ok(l.powf(r)),
-                _ => Err(format!("Unknown operator: {}", op)),
-            }
+        ":format" => match arg {
+            "sci" => state.number_format = NumberFormat::Scientific,
+            "plain" => state.number_format = NumberFormat::Plain,
+            other => return Err(format!("Unknown format: {} (expected 'sci' or 'plain')", other)),
+        },
+        ":exact" => match arg {
+            "" | "on" => state.exact = true,
+            "off" => state.exact = false,
+            other => return Err(format!("Unknown exact setting: {} (expected 'on' or 'off')", other)),
+        },
+        ":bigdecimal" => match arg {
+            "" | "on" => state.decimal = true,
+            "off" => state.decimal = false,
+            other => return Err(format!("Unknown bigdecimal setting: {} (expected 'on' or 'off')", other)),
+        },
+        ":memo" => match arg {
+            "" | "on" => state.memoize = true,
+            "off" => state.memoize = false,
+            other => return Err(format!("Unknown memo setting: {} (expected 'on' or 'off')", other)),
+        },
+        ":trace" => match arg {
+            "" | "on" => state.trace = true,
+            "off" => state.trace = false,
+            other => return Err(format!("Unknown trace setting: {} (expected 'on' or 'off')", other)),
+        },
+        ":ieee" => match arg {
+            "" | "on" => state.ieee_division = true,
+            "off" => state.ieee_division = false,
+            other => return Err(format!("Unknown ieee setting: {} (expected 'on' or 'off')", other)),
+        },
+        ":strict" => match arg {
+            "" | "on" => state.strict_finite = true,
+            "off" => state.strict_finite = false,
+            other => return Err(format!("Unknown strict setting: {} (expected 'on' or 'off')", other)),
+        },
+        ":seed" => {
+            let seed = arg.parse().map_err(|_| format!("Invalid seed: {} (expected a non-negative integer)", arg))?;
+            state.seed(seed);
         }
-        Expr::Assignment { name, value } => {
-            let val = eval(*value, state)?;   // FIXED here
-            state.vars.insert(name, val);
-            Ok(val)
-        }
-        Expr::FunctionCall { name, args } => {
-            if let Some(func) = state.funcs.get(&name).cloned() { // clone tuple to avoid borrow
-                let (param, body) = func;
-                let arg_val = eval(args[0].clone(), state)?;  // safe now
-                if args.len() != 1 {
-                    return Err(format!("Function '{}' expects 1 argument", name));
-                }
-                let mut local_vars = state.vars.clone(); // copy
-                local_vars.insert(param.clone(), arg_val);
-                let mut inner_state = State {
-                    vars: local_vars,
-                    funcs: state.funcs.clone(), // keep global funcs
-                };
-                eval(body.clone(), &mut inner_state)
-            } else {
-                // fallback to built-in
-                let values: Result<Vec<f64>, _> = args.into_iter().map(|a| eval(a, state)).collect();
-                let values = values?;
-                match (name.as_str(), values.as_slice()) {
-                    ("sin", [x]) => Ok(x.sin()),
-                    ("cos", [x]) => Ok(x.cos()),
-                    ("max", [a, b]) => Ok(f64::max(*a, *b)),
-                    _ => Err(format!("Unknown function: {}", name)),
-                }
-            }
+        ":vars" => {
+            let mut vars: Vec<_> = state.vars.iter().collect();
+            vars.sort_by(|a, b| a.0.cmp(b.0));
+            return Ok(vars.iter().map(|(name, value)| format!("{} = {}", name, state.format_value(value))).collect::<Vec<_>>().join("\n"));
         }
-        Expr::FunctionDef { name, param, body } => {
-            state.funcs.insert(name, (param, *body));
-            Ok(0.0) // or just acknowledge
+        ":funcs" => {
+            let mut funcs: Vec<_> = state.funcs.iter().collect();
+            funcs.sort_by(|a, b| a.0.cmp(b.0));
+            return Ok(funcs.iter().map(|(name, func)| format!("{}({})", name, func.0.join(", "))).collect::<Vec<_>>().join("\n"));
         }
-        Expr::Print(expr) => {
-            let value = eval(*expr, state)?;
-            println!("{}", value);
-            Ok(value)
+        ":del" if arg.is_empty() => return Err("Usage: :del <name>".to_string()),
+        ":del" => {
+            eval_program(&[Expr::Delete(arg.to_string())], state).map_err(|e| e.to_string())?;
         }
+        ":save" if arg.is_empty() => return Err("Usage: :save <file>".to_string()),
+        ":save" => fs::write(arg, state.to_script()).map_err(|e| format!("Could not write file {}: {}", arg, e))?,
+        ":load" if arg.is_empty() => return Err("Usage: :load <file>".to_string()),
+        ":load" => load_file(arg, state)?,
+        ":ast" => return parse(arg).map(|expr| ast_tree(&expr)).map_err(|e| e.to_string()),
+        ":tokens" => return token_tree(arg).map_err(|e| e.to_string()),
+        ":clear" => *state = new_state(),
+        _ => return Err(format!("Unknown command: {}", command)),
     }
+    Ok(String::new())
 }
 
-fn run_file(filename: &str, state: &mut State) {
-    match fs::read_to_string(filename) {
-        Ok(contents) => {
-            for (i, line) in contents.lines().enumerate() {
-                if line.trim().is_empty() { continue; }
-                let parse_result = MathParser::parse(Rule::statement, line);
-                match parse_result {
-                    Ok(mut pairs) => {
-                        let expr = parse_expr(pairs.next().expect("..."));
-                        match eval(expr, state) {
-                            Ok(result) => println!("Line {}: {} = {}", i + 1, line, result),
-                            Err(e) => println!("Line {}: Error evaluating '{}': {}", i + 1, line, e),
-                        }
+// In quiet mode (piped input or --quiet) no prompts are shown, results are
+// printed bare and errors go to stderr.
+fn repl(quiet: bool) {
+    let mut state = new_state();
+    let report = |message: String| if quiet { eprintln!("{}", message) } else { println!("{}", message) };
+
+    loop {
+        if !quiet {
+            print!("> ");
+            io::stdout().flush().expect("...");
+        }
+
+        let mut input = String::new();
+        match io::stdin().read_line(&mut input) {
+            Ok(0) => break, // end of input
+            Ok(_) => {}
+            Err(_) => {
+                report("Failed to read input.".to_string());
+                continue;
+            }
+        }
+
+        if input.trim() == "exit" {
+            break;
+        }
+
+        if input.trim().starts_with(':') {
+            match run_command(input.trim(), &mut state) {
+                Ok(output) if output.is_empty() => {}
+                Ok(output) => println!("{}", output),
+                Err(e) => report(format!("Error: {}", e)),
+            }
+            continue;
+        }
+
+        match parse_program(input.trim_end()) {
+            Ok(exprs) => match eval_program(&exprs, &mut state) {
+                Ok(Some(result)) => {
+                    if quiet {
+                        println!("{}", state.format_value(&result));
+                    } else {
+                        println!("= {}", state.format_value(&result));
                     }
-                    Err(e) => println!("Line {}: Parse error: {}", i + 1, e),
+                    state.vars.insert("ans".to_string(), result);
                 }
-            }
+                Ok(None) => {}
+                Err(e) => report(format!("Error: {}", e)),
+            },
+            Err(e) => report(format!("Parse error: {}", e)),
         }
-        Err(e) => println!("Could not read file: {}", e),
     }
 }
 
+// Usage: function [--quiet] [FILE...]
+// Files are run in order against one shared state; with no files the REPL starts.
 fn main() {
-    let mut state = State {
-        vars: HashMap::new(),
-        funcs: HashMap::new(),
-    };
+    let (flags, files): (Vec<String>, Vec<String>) = env::args().skip(1).partition(|arg| arg.starts_with("--"));
+    let quiet = flags.iter().any(|flag| flag == "--quiet") || !io::stdin().is_terminal();
+    // eval recurses once per AST node, so give user recursion enough stack
+    // to reach max_call_depth before the thread itself overflows
+    let succeeded = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            if files.is_empty() {
+                repl(quiet);
+                return true;
+            }
+            // later files may depend on what a failed one didn't finish
+            let mut state = new_state();
+            files.iter().all(|file| run_file(file, &mut state))
+        })
+        .expect("Failed to spawn interpreter thread")
+        .join()
+        .expect("Interpreter thread panicked");
+    if !succeeded {
+        process::exit(1);
+    }
+}
 
-    loop {
-        print!("> ");
-        io::stdout().flush().expect("...");
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let mut input = String::new();
-        if io::stdin().read_line(&mut input).is_err() {
-            println!("Failed to read input.");
-            continue;
+    fn run(state: &mut State, input: &str) {
+        eval_program(&parse_program(input).unwrap(), state).unwrap();
+    }
+
+    fn populated_state() -> State {
+        let mut state = new_state();
+        run(&mut state, "b = 2; a = 1; sq(x) = x^2; add(x, y) = x + y");
+        state
+    }
+
+    #[test]
+    fn vars_and_funcs_list_definitions_sorted_by_name() {
+        let mut state = populated_state();
+        assert_eq!(run_command(":vars", &mut state), Ok("a = 1\nb = 2".to_string()));
+        assert_eq!(run_command(":funcs", &mut state), Ok("add(x, y)\nsq(x)".to_string()));
+    }
+
+    #[test]
+    fn del_removes_a_variable_or_function() {
+        let mut state = populated_state();
+        run_command(":del a", &mut state).unwrap();
+        run_command(":del sq", &mut state).unwrap();
+        assert!(state.get_var("a").is_none());
+        assert!(!state.funcs.contains_key("sq"));
+        assert!(run_command(":del nothing", &mut state).is_err());
+        assert!(run_command(":del", &mut state).is_err());
+    }
+
+    #[test]
+    fn clear_resets_the_state() {
+        let mut state = populated_state();
+        state.angle_mode = AngleMode::Degrees;
+        run_command(":clear", &mut state).unwrap();
+        assert!(state.vars.is_empty() && state.funcs.is_empty());
+        assert_eq!(state.angle_mode, AngleMode::Radians);
+    }
+
+    #[test]
+    fn precision_is_bounded() {
+        let mut state = new_state();
+        run_command(&format!(":precision {}", MAX_PRECISION), &mut state).unwrap();
+        assert_eq!(state.precision, Some(MAX_PRECISION));
+        assert!(run_command(":precision 70000", &mut state).is_err());
+        assert!(run_command(":precision -1", &mut state).is_err());
+        assert_eq!(state.precision, Some(MAX_PRECISION));
+    }
+
+    #[test]
+    fn save_and_load_round_trip_the_session() {
+        let mut state = populated_state();
+        run(&mut state, "v = [1, 2.5]; z = 3 - 4i; s = \"a \\\"b\\\"\"; piece(x) = { x < 0 : -x; x >= 0 : x^2 }");
+        let path = env::temp_dir().join(format!("function-session-{}.fl", process::id()));
+        let path = path.to_str().unwrap();
+        run_command(&format!(":save {}", path), &mut state).unwrap();
+        let mut restored = new_state();
+        run_command(&format!(":load {}", path), &mut restored).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!(restored.vars, state.vars);
+        assert_eq!(restored.to_script(), state.to_script());
+        for (name, func) in &state.funcs {
+            assert!(restored.funcs[name].1.same_structure(&func.1), "{} changed", name);
         }
+        assert!(run_command(":load", &mut restored).is_err());
+    }
 
-        if input.trim() == "exit" {
-            break;
+    #[test]
+    fn ast_command_nests_the_product_under_the_sum() {
+        let tree = run_command(":ast a+b*c", &mut new_state()).unwrap();
+        assert_eq!(tree, "BinaryOp +\n  Variable a\n  BinaryOp *\n    Variable b\n    Variable c");
+        let tokens = run_command(":tokens a+b*c", &mut new_state()).unwrap();
+        assert!(tokens.contains("product \"b*c\""), "{}", tokens);
+        assert!(run_command(":ast 1 +", &mut new_state()).is_err());
+    }
+
+    #[test]
+    fn unknown_commands_are_errors() {
+        assert!(run_command(":frobnicate", &mut new_state()).is_err());
+    }
+
+    #[test]
+    fn mode_command_switches_the_angle_mode() {
+        let mut state = new_state();
+        run_command(":mode deg", &mut state).unwrap();
+        assert_eq!(state.angle_mode, AngleMode::Degrees);
+        run_command(":mode rad", &mut state).unwrap();
+        assert_eq!(state.angle_mode, AngleMode::Radians);
+        assert!(run_command(":mode grad", &mut state).is_err());
+    }
+
+    #[test]
+    fn bare_toggles_switch_a_setting_on() {
+        let mut state = new_state();
+        for command in [":strict", ":ieee", ":memo"] {
+            run_command(command, &mut state).unwrap();
+        }
+        assert!(state.strict_finite && state.ieee_division && state.memoize);
+        for command in [":strict off", ":ieee off", ":memo off"] {
+            run_command(command, &mut state).unwrap();
         }
+        assert!(!state.strict_finite && !state.ieee_division && !state.memoize);
+        assert!(run_command(":strict maybe", &mut state).is_err());
+    }
 
-        let parse_result = MathParser::parse(Rule::statement, &input);
-        match parse_result {
-            Ok(mut pairs) => {
-                let expr = parse_expr(pairs.next().expect("..."));
-                match eval(expr, &mut state) {
-                    Ok(result) => println!("= {}", result),
-                    Err(e) => println!("Error: {}", e),
-                }
-            }
-            Err(e) => println!("Parse error: {}", e),
+    #[test]
+    fn a_statement_continues_onto_the_next_line() {
+        let source = "total = 1 +\n    2\nf(x) = (x +\n  1)\n\nf(total)";
+        let parsed = statements(source);
+        let lines: Vec<_> = parsed.iter().map(|(start, end, _)| (*start, *end)).collect();
+        assert_eq!(lines, [(0, 1), (2, 3), (5, 5)]);
+        let mut state = new_state();
+        for (start, _, statement) in &parsed {
+            eval_program(&parse_statement(*start, statement).unwrap(), &mut state).unwrap();
         }
+        assert_eq!(state.get_var("total"), Some(function::Value::Real(3.0)));
+        assert_eq!(location(0, 1), "Lines 1-2");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn block_comments_are_blanked_keeping_line_numbers() {
+        let source = "x = 1\n/* one\ntwo */\ny = 3 /* three */";
+        let blanked = blank_block_comments(source);
+        assert_eq!(blanked.lines().count(), source.lines().count());
+        assert!(!blanked.contains("one") && !blanked.contains("two") && !blanked.contains("three"));
+        let lines: Vec<_> = statements(&blanked).into_iter().map(|(start, end, _)| (start, end)).collect();
+        assert_eq!(lines, [(0, 0), (3, 3)]);
+    }
+
+    #[test]
+    fn comment_markers_inside_strings_are_text() {
+        let source = "print(\"/* not a comment */\") # a /* comment\nz = 1";
+        assert_eq!(blank_block_comments(source), source);
+    }
+}
@@ -0,0 +1,191 @@
+use crate::value::MAX_SQUARING_EXPONENT;
+use crate::{EvalError, Value, binary_op, real_op};
+
+pub type Matrix = Vec<Vec<f64>>;
+
+// Rows of a list literal such as `[[1, 2], [3, 4]]`, or None unless every
+// item is a non-empty list of real numbers and all have the same length.
+pub fn from_rows(items: &[Value]) -> Option<Matrix> {
+    let rows = items
+        .iter()
+        .map(|item| match item {
+            Value::List(row) if !row.is_empty() => row.iter().map(|x| x.as_real().ok()).collect::<Option<Vec<_>>>(),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let cols = rows.first()?.len();
+    rows.iter().all(|row| row.len() == cols).then_some(rows)
+}
+
+// A list of rows that make up a matrix is that matrix; anything else stays a
+// list.
+pub fn list_or_matrix(items: Vec<Value>) -> Value {
+    from_rows(&items).map_or(Value::List(items), Value::Matrix)
+}
+
+// The rows as lists, for wherever a matrix stands in for the list of lists it
+// was written as.
+pub fn to_rows(m: &Matrix) -> Vec<Value> {
+    m.iter().map(|row| Value::List(row.iter().copied().map(Value::Real).collect())).collect()
+}
+
+pub fn shape(m: &Matrix) -> String {
+    format!("{}x{}", m.len(), m[0].len())
+}
+
+// The matrix as one line per row, with each column right-aligned.
+pub fn format_matrix(m: &Matrix, format_real: impl Fn(f64) -> String) -> String {
+    let cells: Vec<Vec<String>> = m.iter().map(|row| row.iter().map(|x| format_real(*x)).collect()).collect();
+    let widths: Vec<usize> = (0..m[0].len()).map(|j| cells.iter().map(|row| row[j].len()).max().unwrap_or(0)).collect();
+    let rows: Vec<String> = cells
+        .iter()
+        .map(|row| {
+            let padded: Vec<String> = row.iter().zip(&widths).map(|(cell, width)| format!("{:>width$}", cell, width = width)).collect();
+            format!("[{}]", padded.join(", "))
+        })
+        .collect();
+    format!("[{}]", rows.join(",\n "))
+}
+
+// Arithmetic with at least one matrix operand: `*` between matrices (or a
+// matrix and a vector) is the matrix product, `+` and `-` between matrices
+// are element-wise, `^` raises a square matrix to an integer power, and a
+// scalar applies to every element. Other operators treat a matrix as the list
+// of its rows.
+pub fn matrix_op(l: &Value, op: &str, r: &Value, ieee_division: bool) -> Result<Value, EvalError> {
+    match (l, r) {
+        (Value::Matrix(a), Value::Matrix(b)) if op == "*" => multiply(a, b).map(Value::Matrix),
+        (Value::Matrix(a), Value::Matrix(b)) if op == "+" || op == "-" => {
+            if shape(a) != shape(b) {
+                return Err(format!("Matrix dimension mismatch: {} {} {}", shape(a), op, shape(b)).into());
+            }
+            let rows = a.iter().zip(b).map(|(x, y)| x.iter().zip(y).map(|(x, y)| real_op(*x, op, *y)).collect());
+            rows.collect::<Result<_, _>>().map(Value::Matrix)
+        }
+        (Value::Matrix(a), Value::List(v)) if op == "*" => {
+            let column = v.iter().map(|x| vec![x.as_real().unwrap_or(f64::NAN)]).collect::<Vec<_>>();
+            if v.is_empty() || v.iter().any(|x| x.as_real().is_err()) {
+                return Err("Matrix-vector product needs a list of real numbers".into());
+            }
+            let product = multiply(a, &column)?;
+            Ok(Value::List(product.into_iter().map(|row| Value::Real(row[0])).collect()))
+        }
+        (Value::Matrix(a), exp) if op == "^" => {
+            let exp = exp.as_real()?;
+            if exp.fract() != 0.0 || !exp.is_finite() {
+                return Err(format!("Matrix power must be an integer, got {}", exp).into());
+            }
+            if exp.abs() > MAX_SQUARING_EXPONENT {
+                return Err(format!("Matrix power is too large: {}", exp).into());
+            }
+            let n = square_size("^", a)?;
+            // repeated squaring takes one step per bit of the exponent
+            let mut square = if exp < 0.0 { inverse(a)? } else { a.clone() };
+            let (mut result, mut k) = (identity(n), exp.abs() as u64);
+            while k > 0 {
+                if k & 1 == 1 {
+                    result = multiply(&result, &square)?;
+                }
+                k >>= 1;
+                if k > 0 {
+                    square = multiply(&square, &square)?;
+                }
+            }
+            Ok(Value::Matrix(result))
+        }
+        (Value::Matrix(a), scalar) if matches!(op, "+" | "-" | "*" | "/") && !matches!(scalar, Value::List(_)) => {
+            let s = scalar.as_real()?;
+            if op == "/" && s == 0.0 {
+                return Err(EvalError::DivByZero);
+            }
+            elementwise(a, |x| real_op(x, op, s))
+        }
+        (scalar, Value::Matrix(b)) if matches!(op, "+" | "-" | "*") && !matches!(scalar, Value::List(_)) => {
+            let s = scalar.as_real()?;
+            elementwise(b, |x| real_op(s, op, x))
+        }
+        _ => {
+            let as_list = |value: &Value| match value {
+                Value::Matrix(m) => Value::List(to_rows(m)),
+                value => value.clone(),
+            };
+            Ok(match binary_op(&as_list(l), op, &as_list(r), ieee_division)? {
+                Value::List(items) => list_or_matrix(items),
+                value => value,
+            })
+        }
+    }
+}
+
+fn elementwise(m: &Matrix, mut f: impl FnMut(f64) -> Result<f64, EvalError>) -> Result<Value, EvalError> {
+    m.iter().map(|row| row.iter().map(|x| f(*x)).collect()).collect::<Result<_, _>>().map(Value::Matrix)
+}
+
+pub fn multiply(a: &Matrix, b: &Matrix) -> Result<Matrix, EvalError> {
+    if a[0].len() != b.len() {
+        return Err(format!("Matrix dimension mismatch: {} * {}", shape(a), shape(b)).into());
+    }
+    Ok(a.iter().map(|row| (0..b[0].len()).map(|j| row.iter().zip(b).map(|(x, b_row)| x * b_row[j]).sum()).collect()).collect())
+}
+
+pub fn transpose(m: &Matrix) -> Matrix {
+    (0..m[0].len()).map(|j| m.iter().map(|row| row[j]).collect()).collect()
+}
+
+fn identity(n: usize) -> Matrix {
+    (0..n).map(|i| (0..n).map(|j| if i == j { 1.0 } else { 0.0 }).collect()).collect()
+}
+
+fn square_size(func: &str, m: &Matrix) -> Result<usize, EvalError> {
+    if m.len() != m[0].len() {
+        return Err(format!("{} expects a square matrix, got {}", func, shape(m)).into());
+    }
+    Ok(m.len())
+}
+
+// Gaussian elimination with partial pivoting.
+pub fn determinant(m: &Matrix) -> Result<f64, EvalError> {
+    let n = square_size("det", m)?;
+    let mut a = m.clone();
+    let mut det = 1.0;
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs())).unwrap_or(col);
+        if a[pivot][col] == 0.0 {
+            return Ok(0.0);
+        }
+        if pivot != col {
+            a.swap(pivot, col);
+            det = -det;
+        }
+        det *= a[col][col];
+        for row in col + 1..n {
+            let factor = a[row][col] / a[col][col];
+            let pivot_row = a[col].clone();
+            a[row].iter_mut().zip(pivot_row).skip(col).for_each(|(x, p)| *x -= factor * p);
+        }
+    }
+    Ok(det)
+}
+
+// Gauss-Jordan elimination with partial pivoting on [m | I].
+pub fn inverse(m: &Matrix) -> Result<Matrix, EvalError> {
+    let n = square_size("inv", m)?;
+    let mut a: Matrix = m.iter().zip(identity(n)).map(|(row, id)| row.iter().copied().chain(id).collect()).collect();
+    for col in 0..n {
+        let pivot = (col..n).max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs())).unwrap_or(col);
+        if a[pivot][col].abs() < f64::EPSILON {
+            return Err("Matrix is singular".into());
+        }
+        a.swap(pivot, col);
+        let scale = a[col][col];
+        a[col].iter_mut().for_each(|x| *x /= scale);
+        for row in 0..n {
+            if row != col {
+                let factor = a[row][col];
+                let pivot_row = a[col].clone();
+                a[row].iter_mut().zip(pivot_row).for_each(|(x, p)| *x -= factor * p);
+            }
+        }
+    }
+    Ok(a.into_iter().map(|row| row[n..].to_vec()).collect())
+}
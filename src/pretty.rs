@@ -0,0 +1,200 @@
+use crate::{Expr, Value};
+
+// Binding strength of each grammar level, loosest first. A subexpression is
+// parenthesized only when it binds more loosely than its position requires.
+const LAMBDA: u8 = 0;
+const CONDITIONAL: u8 = 1;
+const COALESCE: u8 = 2;
+const COMPARISON: u8 = 3;
+const SUM: u8 = 4;
+const PRODUCT: u8 = 5;
+const POWER: u8 = 6;
+const UNARY: u8 = 7;
+const POSTFIX: u8 = 8;
+const PRIMARY: u8 = 9;
+
+// Turns an expression back into source that parses to the same tree, using
+// only the parentheses that precedence requires.
+pub fn expr_to_string(expr: &Expr) -> String {
+    match expr {
+        Expr::Number(n) => number_to_string(*n),
+        Expr::Str(text) => string_literal(text),
+        Expr::Variable(name, _) | Expr::Unit(name) => name.clone(),
+        Expr::UnaryOp { op, expr } if op == "!" => format!("{}!", at(expr, PRIMARY)),
+        Expr::UnaryOp { op, expr } => format!("{}{}", op, at(expr, POSTFIX)),
+        // a quantity literal such as `50 cm`
+        Expr::BinaryOp { left, op, right } if matches!((&**left, &**right), (Expr::Number(n), Expr::Unit(_)) if *n >= 0.0) && op == "*" => {
+            format!("{} {}", expr_to_string(left), expr_to_string(right))
+        }
+        Expr::BinaryOp { left, op, right } => {
+            // the operand positions allowed by the grammar at each level
+            let (lhs, rhs) = match precedence(expr) {
+                COALESCE => (COALESCE, COMPARISON),
+                COMPARISON => (SUM, SUM),
+                SUM => (SUM, PRODUCT),
+                PRODUCT => (PRODUCT, POWER),
+                _ => (UNARY, POWER),
+            };
+            match op.as_str() {
+                "^" => format!("{}^{}", at(left, lhs), at(right, rhs)),
+                _ => format!("{} {} {}", at(left, lhs), op, at(right, rhs)),
+            }
+        }
+        Expr::Conditional { cond, then, otherwise } => {
+            format!("{} ? {} : {}", at(cond, COALESCE), expr_to_string(then), expr_to_string(otherwise))
+        }
+        Expr::Let { name, value, body } => {
+            format!("let {} = {} in {}", name, expr_to_string(value), expr_to_string(body))
+        }
+        Expr::List(items) => format!("[{}]", join(items)),
+        Expr::Lambda { params, body } => match params.as_slice() {
+            [param] => format!("{} -> {}", param, expr_to_string(body)),
+            _ => format!("({}) -> {}", params.join(", "), expr_to_string(body)),
+        },
+        Expr::Piecewise(pieces) => {
+            let pieces: Vec<_> = pieces
+                .iter()
+                .map(|(guard, value)| format!("{}: {}", expr_to_string(guard), expr_to_string(value)))
+                .collect();
+            format!("{{ {} }}", pieces.join("; "))
+        }
+        Expr::Index { target, index } => format!("{}[{}]", at(target, PRIMARY), expr_to_string(index)),
+        Expr::Slice { target, start, end } => {
+            let bound = |bound: &Option<Box<Expr>>| bound.as_deref().map(expr_to_string).unwrap_or_default();
+            format!("{}[{}:{}]", at(target, PRIMARY), bound(start), bound(end))
+        }
+        Expr::FunctionCall { name, args, .. } => format!("{}({})", name, join(args)),
+        Expr::Assignment { name, value } => format!("{} = {}", name, expr_to_string(value)),
+        Expr::FunctionDef { name, params, body } => {
+            format!("{}({}) = {}", name, params.join(", "), expr_to_string(body))
+        }
+        Expr::Print(exprs) => format!("print({})", join(exprs)),
+        Expr::Delete(name) => format!("del {}", name),
+    }
+}
+
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Number(n) if *n < 0.0 => UNARY,
+        Expr::BinaryOp { op, .. } => match op.as_str() {
+            "+" | "-" => SUM,
+            "*" | "/" | "%" => PRODUCT,
+            "^" => POWER,
+            "??" => COALESCE,
+            _ => COMPARISON,
+        },
+        Expr::UnaryOp { op, .. } if op == "!" => POSTFIX,
+        Expr::UnaryOp { .. } => UNARY,
+        Expr::Conditional { .. } => CONDITIONAL,
+        Expr::Index { .. } | Expr::Slice { .. } => POSTFIX,
+        Expr::Number(_) | Expr::Str(_) | Expr::Variable(..) | Expr::Unit(_) | Expr::FunctionCall { .. } | Expr::List(_) | Expr::Piecewise(_) => PRIMARY,
+        Expr::Lambda { .. } | Expr::Let { .. } | Expr::Assignment { .. } | Expr::FunctionDef { .. } | Expr::Print(_) | Expr::Delete(_) => {
+            LAMBDA
+        }
+    }
+}
+
+// `expr` in a position that needs at least `min` binding strength.
+fn at(expr: &Expr, min: u8) -> String {
+    if precedence(expr) < min { format!("({})", expr_to_string(expr)) } else { expr_to_string(expr) }
+}
+
+// The expression as an indented tree, one node per line with its children
+// two spaces further in.
+pub fn ast_tree(expr: &Expr) -> String {
+    let mut out = String::new();
+    write_tree(expr, 0, &mut out);
+    out.trim_end().to_string()
+}
+
+fn write_tree(expr: &Expr, depth: usize, out: &mut String) {
+    let indent = "  ".repeat(depth);
+    let (label, children): (String, Vec<&Expr>) = match expr {
+        Expr::Number(n) => (format!("Number {}", n), vec![]),
+        Expr::Str(text) => (format!("Str {}", string_literal(text)), vec![]),
+        Expr::Variable(name, _) => (format!("Variable {}", name), vec![]),
+        Expr::Unit(name) => (format!("Unit {}", name), vec![]),
+        Expr::UnaryOp { op, expr } => (format!("UnaryOp {}", op), vec![expr]),
+        Expr::BinaryOp { left, op, right } => (format!("BinaryOp {}", op), vec![left, right]),
+        Expr::Conditional { cond, then, otherwise } => ("Conditional".to_string(), vec![cond, then, otherwise]),
+        Expr::Let { name, value, body } => (format!("Let {}", name), vec![value, body]),
+        Expr::List(items) => ("List".to_string(), items.iter().collect()),
+        Expr::Lambda { params, body } => (format!("Lambda ({})", params.join(", ")), vec![body]),
+        Expr::Piecewise(pieces) => {
+            ("Piecewise".to_string(), pieces.iter().flat_map(|(guard, value)| [guard, value]).collect())
+        }
+        Expr::Index { target, index } => ("Index".to_string(), vec![target, index]),
+        Expr::Slice { target, start, end } => {
+            let bounds = start.iter().chain(end).map(|bound| &**bound);
+            let shape = format!("[{}:{}]", if start.is_some() { "start" } else { "" }, if end.is_some() { "end" } else { "" });
+            (format!("Slice {}", shape), std::iter::once(&**target).chain(bounds).collect())
+        }
+        Expr::FunctionCall { name, args, .. } => (format!("FunctionCall {}", name), args.iter().collect()),
+        Expr::Assignment { name, value } => (format!("Assignment {}", name), vec![value]),
+        Expr::FunctionDef { name, params, body } => {
+            (format!("FunctionDef {}({})", name, params.join(", ")), vec![body])
+        }
+        Expr::Print(exprs) => ("Print".to_string(), exprs.iter().collect()),
+        Expr::Delete(name) => (format!("Delete {}", name), vec![]),
+    };
+    out.push_str(&format!("{}{}\n", indent, label));
+    for child in children {
+        write_tree(child, depth + 1, out);
+    }
+}
+
+// Source for a value, as the right-hand side of an assignment. Lambdas lose
+// the bindings they captured.
+pub fn value_to_source(value: &Value) -> String {
+    match value {
+        Value::Real(x) => number_to_string(*x),
+        Value::Rational(num, 1) => num.to_string(),
+        Value::Rational(num, den) => format!("{}/{}", num, den),
+        Value::Decimal(..) => value.to_string(),
+        Value::Complex(re, im) => {
+            let sign = if *im < 0.0 { '-' } else { '+' };
+            format!("{} {} {}*i", number_to_string(*re), sign, number_to_string(im.abs()))
+        }
+        Value::Quantity(x, dims) if x.is_finite() => format!("{} {}", number_to_string(*x), crate::units::unit_source(dims)),
+        Value::Quantity(x, dims) => format!("{} * 1 {}", number_to_string(*x), crate::units::unit_source(dims)),
+        Value::List(items) => format!("[{}]", items.iter().map(value_to_source).collect::<Vec<_>>().join(", ")),
+        Value::Matrix(rows) => {
+            let row = |row: &Vec<f64>| format!("[{}]", row.iter().map(|x| number_to_string(*x)).collect::<Vec<_>>().join(", "));
+            format!("[{}]", rows.iter().map(row).collect::<Vec<_>>().join(", "))
+        }
+        Value::Str(text) => string_literal(text),
+        Value::Function(name) => name.clone(),
+        Value::Lambda(lambda) => {
+            expr_to_string(&Expr::Lambda { params: lambda.params.clone(), body: Box::new(lambda.body.clone()) })
+        }
+    }
+}
+
+fn number_to_string(n: f64) -> String {
+    match n {
+        _ if n.is_nan() => "nan".to_string(),
+        f64::INFINITY => "inf".to_string(),
+        f64::NEG_INFINITY => "-inf".to_string(),
+        _ => n.to_string(),
+    }
+}
+
+// `text` quoted, with the escapes the grammar understands
+fn string_literal(text: &str) -> String {
+    let mut out = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn join(exprs: &[Expr]) -> String {
+    exprs.iter().map(expr_to_string).collect::<Vec<_>>().join(", ")
+}
@@ -0,0 +1,112 @@
+// Exponents of the SI base units, in the order of BASE_UNITS.
+pub type Dims = [i8; 7];
+
+pub const DIMENSIONLESS: Dims = [0; 7];
+const BASE_UNITS: [&str; 7] = ["m", "kg", "s", "A", "K", "mol", "cd"];
+
+// Size of one `name` in SI base units, and its dimensions.
+pub fn lookup_unit(name: &str) -> Option<(f64, Dims)> {
+    let (length, mass, time) = (0, 1, 2);
+    let base = |dim: usize| {
+        let mut dims = DIMENSIONLESS;
+        dims[dim] = 1;
+        dims
+    };
+    Some(match name {
+        "m" => (1.0, base(length)),
+        "km" => (1000.0, base(length)),
+        "cm" => (0.01, base(length)),
+        "mm" => (0.001, base(length)),
+        "kg" => (1.0, base(mass)),
+        "g" => (0.001, base(mass)),
+        "s" => (1.0, base(time)),
+        "ms" => (0.001, base(time)),
+        "min" => (60.0, base(time)),
+        "h" => (3600.0, base(time)),
+        "A" => (1.0, base(3)),
+        "K" => (1.0, base(4)),
+        "mol" => (1.0, base(5)),
+        "cd" => (1.0, base(6)),
+        "Hz" => (1.0, [0, 0, -1, 0, 0, 0, 0]),
+        "N" => (1.0, [1, 1, -2, 0, 0, 0, 0]),
+        "J" => (1.0, [2, 1, -2, 0, 0, 0, 0]),
+        "W" => (1.0, [2, 1, -3, 0, 0, 0, 0]),
+        _ => return None,
+    })
+}
+
+// Size and dimensions of a unit as written after a number, such as `m`,
+// `m/s^2` or `kg*m/(s*A)`. Operators apply left to right.
+pub fn parse_unit(text: &str) -> Option<(f64, Dims)> {
+    let (mut size, mut dims) = (1.0, DIMENSIONLESS);
+    let mut sign = 1;
+    let mut rest = text;
+    loop {
+        // a parenthesized group is divided out as a whole
+        let (group, after) = match rest.strip_prefix('(') {
+            Some(inner) => inner.split_once(')')?,
+            None => rest.split_at(rest.find(['*', '/']).unwrap_or(rest.len())),
+        };
+        for factor in group.split('*') {
+            let (name, exp) = match factor.split_once('^') {
+                Some((name, exp)) => (name, exp.parse::<i8>().ok()?),
+                None => (factor, 1),
+            };
+            let exp = exp.checked_mul(sign)?;
+            let (factor_size, factor_dims) = lookup_unit(name)?;
+            size *= factor_size.powi(exp as i32);
+            for (total, dim) in dims.iter_mut().zip(factor_dims) {
+                *total = total.checked_add(dim.checked_mul(exp)?)?;
+            }
+        }
+        sign = match after.chars().next() {
+            None => return Some((size, dims)),
+            Some('*') => 1,
+            Some('/') => -1,
+            Some(_) => return None,
+        };
+        rest = &after[1..];
+    }
+}
+
+// Dimensions of a product (sign 1) or quotient (sign -1), or None if an
+// exponent leaves the range of i8.
+pub fn combine(a: Dims, b: Dims, sign: i8) -> Option<Dims> {
+    let mut dims = DIMENSIONLESS;
+    for (i, dim) in dims.iter_mut().enumerate() {
+        *dim = a[i].checked_add(b[i].checked_mul(sign)?)?;
+    }
+    Some(dims)
+}
+
+// Dimensions in base units, e.g. `m/s` or `kg*m^2/s^2`.
+pub fn format_dims(dims: &Dims) -> String {
+    let part = |unit: &str, exp: i8| if exp == 1 { unit.to_string() } else { format!("{}^{}", unit, exp) };
+    let join = |positive: bool| {
+        BASE_UNITS
+            .iter()
+            .zip(dims)
+            .filter(|(_, exp)| if positive { **exp > 0 } else { **exp < 0 })
+            .map(|(unit, exp)| part(unit, exp.abs()))
+            .collect::<Vec<_>>()
+            .join("*")
+    };
+    let (numerator, denominator) = (join(true), join(false));
+    match (numerator.is_empty(), denominator.is_empty()) {
+        (_, true) => numerator,
+        (true, false) => format!("1/{}", denominator),
+        (false, false) if denominator.contains('*') => format!("{}/({})", numerator, denominator),
+        (false, false) => format!("{}/{}", numerator, denominator),
+    }
+}
+
+// Dimensions as parse_unit reads them back: format_dims, except that with
+// nothing in the numerator the exponents go negative (`s^-1`, not `1/s`).
+pub fn unit_source(dims: &Dims) -> String {
+    let text = format_dims(dims);
+    if !text.starts_with("1/") {
+        return text;
+    }
+    let factors = BASE_UNITS.iter().zip(dims).filter(|(_, exp)| **exp != 0).map(|(unit, exp)| format!("{}^{}", unit, exp));
+    factors.collect::<Vec<_>>().join("*")
+}
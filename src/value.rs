@@ -0,0 +1,325 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::matrix;
+use crate::units::{self, Dims};
+use crate::{EvalError, Expr, gcd};
+
+// A runtime value. Arithmetic stays on Real until a result needs an imaginary
+// part, and complex results with a zero imaginary part collapse back to Real.
+// Rationals only appear in exact mode and degrade to Real whenever a result
+// can't be represented exactly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Real(f64),
+    Complex(f64, f64),
+    Rational(i64, i64),
+    // mantissa * 10^-scale, with no trailing zeros in the mantissa
+    Decimal(i128, u32),
+    // a magnitude in SI base units and the exponent of each base unit
+    Quantity(f64, Dims),
+    List(Vec<Value>),
+    // rows of equal, non-zero length
+    Matrix(Vec<Vec<f64>>),
+    // text, such as the result of hex()
+    Str(String),
+    Lambda(Rc<Lambda>),
+    // a named user, native or built-in function
+    Function(String),
+}
+
+// An anonymous function together with the local bindings that were in scope
+// when it was created.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lambda {
+    pub params: Vec<String>,
+    pub body: Expr,
+    pub captured: HashMap<String, Value>,
+}
+
+// Largest number of decimal places an exact-mode literal is converted with.
+const MAX_EXACT_DECIMALS: u32 = 9;
+// Most digits after the point a decimal quotient may have; one that needs
+// more has no exact decimal form.
+const MAX_DECIMAL_PLACES: u32 = 28;
+// Largest exponent complex_pow and matrix powers raise to by repeated squaring: every integer
+// up to 2^53 is exact as an f64.
+pub(crate) const MAX_SQUARING_EXPONENT: f64 = 9_007_199_254_740_992.0;
+
+impl Value {
+    pub fn complex(re: f64, im: f64) -> Value {
+        if im == 0.0 { Value::Real(re) } else { Value::Complex(re, im) }
+    }
+
+    // A quantity, or a plain Real once the units cancel out.
+    pub fn quantity(x: f64, dims: Dims) -> Value {
+        if dims == units::DIMENSIONLESS { Value::Real(x) } else { Value::Quantity(x, dims) }
+    }
+
+    // Reduced fraction with a positive denominator, or None if `den` is zero
+    // or the sign fix-up overflows.
+    pub fn rational(num: i64, den: i64) -> Option<Value> {
+        if den == 0 || num == i64::MIN || den == i64::MIN {
+            return None;
+        }
+        // neither is i64::MIN, so the gcd fits in an i64
+        let g = gcd(num, den) as i64;
+        let (num, den) = (num / g, den / g);
+        if den < 0 {
+            Some(Value::Rational(num.checked_neg()?, den.checked_neg()?))
+        } else {
+            Some(Value::Rational(num, den))
+        }
+    }
+
+    // Exact form of a literal such as `3` or `0.25`, falling back to Real for
+    // values with too many decimal places.
+    pub fn exact(x: f64) -> Value {
+        for places in 0..=MAX_EXACT_DECIMALS {
+            let den = 10i64.pow(places);
+            let scaled = x * den as f64;
+            if scaled.fract() == 0.0
+                && scaled.abs() < i64::MAX as f64
+                && let Some(value) = Value::rational(scaled as i64, den)
+            {
+                return value;
+            }
+        }
+        Value::Real(x)
+    }
+
+    // Decimal form of a literal, read from its shortest round-trip digits so
+    // that `0.1` is exactly one tenth. Falls back to Real if it doesn't fit.
+    pub fn decimal(x: f64) -> Value {
+        let text = x.to_string();
+        let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+        match format!("{}{}", int, frac).parse() {
+            Ok(mantissa) if x.is_finite() => Value::decimal_parts(mantissa, frac.len() as u32),
+            _ => Value::Real(x),
+        }
+    }
+
+    pub fn decimal_parts(mut mantissa: i128, mut scale: u32) -> Value {
+        while scale > 0 && mantissa % 10 == 0 {
+            mantissa /= 10;
+            scale -= 1;
+        }
+        Value::Decimal(mantissa, scale)
+    }
+
+    // (mantissa, scale) of decimals and of integer fractions.
+    pub fn as_decimal(&self) -> Option<(i128, u32)> {
+        match self {
+            Value::Decimal(mantissa, scale) => Some((*mantissa, *scale)),
+            Value::Rational(num, 1) => Some((*num as i128, 0)),
+            _ => None,
+        }
+    }
+
+    pub fn as_real(&self) -> Result<f64, EvalError> {
+        match self {
+            Value::Real(x) => Ok(*x),
+            Value::Decimal(..) => Ok(self.to_string().parse().unwrap_or(f64::NAN)),
+            Value::Rational(num, den) => Ok(*num as f64 / *den as f64),
+            Value::Complex(..) | Value::Quantity(..) | Value::List(_) | Value::Matrix(_) | Value::Str(_) | Value::Lambda(_) | Value::Function(_) => Err(format!("Expected a real number, got {}", self).into()),
+        }
+    }
+
+    // (real, imaginary) parts; reals have a zero imaginary part. Lists have
+    // no scalar parts, so callers map over their elements before asking.
+    pub fn parts(&self) -> (f64, f64) {
+        match self {
+            Value::Real(x) => (*x, 0.0),
+            Value::Rational(num, den) => (*num as f64 / *den as f64, 0.0),
+            Value::Decimal(..) => (self.as_real().unwrap_or(f64::NAN), 0.0),
+            Value::Complex(re, im) => (*re, *im),
+            Value::Quantity(..) | Value::List(_) | Value::Matrix(_) | Value::Str(_) | Value::Lambda(_) | Value::Function(_) => (f64::NAN, f64::NAN),
+        }
+    }
+
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Value::Real(x) | Value::Quantity(x, _) => x.is_finite(),
+            Value::Complex(re, im) => re.is_finite() && im.is_finite(),
+            Value::Rational(..) | Value::Decimal(..) | Value::Str(_) | Value::Lambda(_) | Value::Function(_) => true,
+            Value::List(items) => items.iter().all(Value::is_finite),
+            Value::Matrix(rows) => rows.iter().flatten().all(|x| x.is_finite()),
+        }
+    }
+
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::List(items) => !items.is_empty(),
+            Value::Str(text) => !text.is_empty(),
+            Value::Quantity(x, _) => *x != 0.0,
+            Value::Matrix(_) | Value::Lambda(_) | Value::Function(_) => true,
+            _ => {
+                let (re, im) = self.parts();
+                re != 0.0 || im != 0.0
+            }
+        }
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Self {
+        Value::Real(x)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Real(x) => write!(f, "{}", x),
+            Value::Rational(num, 1) => write!(f, "{}", num),
+            Value::Rational(num, den) => write!(f, "{}/{}", num, den),
+            Value::Decimal(mantissa, 0) => write!(f, "{}", mantissa),
+            Value::Decimal(mantissa, scale) => {
+                let digits = format!("{:0>width$}", mantissa.unsigned_abs(), width = *scale as usize + 1);
+                let (int, frac) = digits.split_at(digits.len() - *scale as usize);
+                write!(f, "{}{}.{}", if *mantissa < 0 { "-" } else { "" }, int, frac)
+            }
+            Value::Complex(re, im) => write!(f, "{}", format_complex(*re, *im, |x| x.to_string())),
+            Value::Quantity(x, dims) => write!(f, "{} {}", x, units::format_dims(dims)),
+            Value::List(items) => write!(f, "{}", format_list(items, |x| x.to_string())),
+            Value::Matrix(rows) => write!(f, "{}", matrix::format_matrix(rows, |x| x.to_string())),
+            Value::Str(text) => write!(f, "{}", text),
+            Value::Lambda(lambda) => write!(f, "<function({})>", lambda.params.join(", ")),
+            Value::Function(name) => write!(f, "<function {}>", name),
+        }
+    }
+}
+
+// Formats as `a + bi`, `a - bi`, or `bi` when there is no real part.
+pub fn format_complex(re: f64, im: f64, format_real: impl Fn(f64) -> String) -> String {
+    if re == 0.0 {
+        return format!("{}i", format_real(im));
+    }
+    let sign = if im < 0.0 { '-' } else { '+' };
+    format!("{} {} {}i", format_real(re), sign, format_real(im.abs()))
+}
+
+// Exact arithmetic on two fractions. Returns None when the operator has no
+// exact result here (e.g. a fractional power) or an intermediate overflows,
+// in which case the caller falls back to floating point.
+pub fn rational_op((a, b): (i64, i64), op: &str, (c, d): (i64, i64)) -> Option<Value> {
+    match op {
+        "+" => Value::rational(a.checked_mul(d)?.checked_add(c.checked_mul(b)?)?, b.checked_mul(d)?),
+        "-" => Value::rational(a.checked_mul(d)?.checked_sub(c.checked_mul(b)?)?, b.checked_mul(d)?),
+        "*" => Value::rational(a.checked_mul(c)?, b.checked_mul(d)?),
+        "/" => Value::rational(a.checked_mul(d)?, b.checked_mul(c)?),
+        "^" if d == 1 => {
+            let exp = u32::try_from(c.unsigned_abs()).ok()?;
+            let (num, den) = (a.checked_pow(exp)?, b.checked_pow(exp)?);
+            if c < 0 { Value::rational(den, num) } else { Value::rational(num, den) }
+        }
+        _ => None,
+    }
+}
+
+// Exact arithmetic on two decimals. Returns None where rational_op would, and
+// for a quotient that doesn't terminate within MAX_DECIMAL_PLACES digits, and
+// the caller falls back to floating point.
+pub fn decimal_op((a, s): (i128, u32), op: &str, (b, t): (i128, u32)) -> Option<Value> {
+    let scale = s.max(t);
+    let a_aligned = a.checked_mul(10i128.checked_pow(scale - s)?)?;
+    let b_aligned = b.checked_mul(10i128.checked_pow(scale - t)?)?;
+    let truth = |holds: bool| Some(Value::Real(if holds { 1.0 } else { 0.0 }));
+    match op {
+        "+" => Some(Value::decimal_parts(a_aligned.checked_add(b_aligned)?, scale)),
+        "-" => Some(Value::decimal_parts(a_aligned.checked_sub(b_aligned)?, scale)),
+        "*" => Some(Value::decimal_parts(a.checked_mul(b)?, s.checked_add(t)?)),
+        "/" if b == 0 => None,
+        "/" => {
+            // long division, one digit after the point at a time
+            let (mut quotient, mut rem, mut places) = (a_aligned / b_aligned, a_aligned % b_aligned, 0);
+            while rem != 0 && places < MAX_DECIMAL_PLACES {
+                rem = rem.checked_mul(10)?;
+                quotient = quotient.checked_mul(10)?.checked_add(rem / b_aligned)?;
+                rem %= b_aligned;
+                places += 1;
+            }
+            // cutting the digits off would lose the rest silently, and turn
+            // a small quotient into 0
+            if rem != 0 {
+                return None;
+            }
+            Some(Value::decimal_parts(quotient, places))
+        }
+        "%" => Some(Value::decimal_parts(a_aligned.checked_rem_euclid(b_aligned)?, scale)),
+        "^" if t == 0 && b < 0 => decimal_op((1, 0), "/", decimal_op((a, s), "^", (b.checked_neg()?, 0))?.as_decimal()?),
+        "^" if t == 0 => {
+            let exp = u32::try_from(b).ok()?;
+            Some(Value::decimal_parts(a.checked_pow(exp)?, s.checked_mul(exp)?))
+        }
+        "==" => truth(a_aligned == b_aligned),
+        "!=" => truth(a_aligned != b_aligned),
+        "<" => truth(a_aligned < b_aligned),
+        ">" => truth(a_aligned > b_aligned),
+        "<=" => truth(a_aligned <= b_aligned),
+        ">=" => truth(a_aligned >= b_aligned),
+        _ => None,
+    }
+}
+
+// Exact square root of a fraction whose numerator and denominator are both
+// perfect squares.
+pub fn rational_sqrt((num, den): (i64, i64)) -> Option<Value> {
+    let root = |n: i64| {
+        let r = (n as f64).sqrt().round() as i64;
+        (n >= 0 && r.checked_mul(r) == Some(n)).then_some(r)
+    };
+    Value::rational(root(num)?, root(den)?)
+}
+
+pub fn format_list(items: &[Value], format_item: impl Fn(&Value) -> String) -> String {
+    format!("[{}]", items.iter().map(format_item).collect::<Vec<_>>().join(", "))
+}
+
+pub fn complex_mul((a, b): (f64, f64), (c, d): (f64, f64)) -> (f64, f64) {
+    (a * c - b * d, a * d + b * c)
+}
+
+pub fn complex_div((a, b): (f64, f64), (c, d): (f64, f64)) -> (f64, f64) {
+    let denom = c * c + d * d;
+    ((a * c + b * d) / denom, (b * c - a * d) / denom)
+}
+
+pub fn complex_exp((re, im): (f64, f64)) -> (f64, f64) {
+    let scale = re.exp();
+    (scale * im.cos(), scale * im.sin())
+}
+
+// principal branch
+pub fn complex_ln((re, im): (f64, f64)) -> (f64, f64) {
+    (re.hypot(im).ln(), im.atan2(re))
+}
+
+pub fn complex_pow(base: (f64, f64), exponent: (f64, f64)) -> (f64, f64) {
+    if base == (0.0, 0.0) {
+        return if exponent == (0.0, 0.0) { (1.0, 0.0) } else { (0.0, 0.0) };
+    }
+    // integer powers by repeated squaring stay exact where they can, so i^2
+    // is -1 rather than -1 + 1.2e-16i
+    if exponent.1 == 0.0 && exponent.0.fract() == 0.0 && exponent.0.abs() <= MAX_SQUARING_EXPONENT {
+        let (mut result, mut square, mut n) = ((1.0, 0.0), base, exponent.0.abs() as u64);
+        while n > 0 {
+            if n & 1 == 1 {
+                result = complex_mul(result, square);
+            }
+            square = complex_mul(square, square);
+            n >>= 1;
+        }
+        return if exponent.0 < 0.0 { complex_div((1.0, 0.0), result) } else { result };
+    }
+    complex_exp(complex_mul(exponent, complex_ln(base)))
+}
+
+// principal square root
+pub fn complex_sqrt((re, im): (f64, f64)) -> (f64, f64) {
+    let modulus = re.hypot(im);
+    let real = ((modulus + re) / 2.0).sqrt();
+    let imag = ((modulus - re) / 2.0).sqrt();
+    (real, if im < 0.0 { -imag } else { imag })
+}
@@ -0,0 +1,108 @@
+use function::{EvalError, State, Value, evaluate};
+
+#[test]
+fn evaluate_runs_against_a_shared_state() {
+    let mut state = State::new();
+    assert_eq!(evaluate("1 + 2 * 3", &mut state), Ok(Value::Real(7.0)));
+    assert_eq!(evaluate("r = 2", &mut state), Ok(Value::Real(2.0)));
+    assert_eq!(evaluate("sq(x) = x * x", &mut state).map(|_| ()), Ok(()));
+    assert_eq!(evaluate("sq(r) + 1", &mut state), Ok(Value::Real(5.0)));
+}
+
+#[test]
+fn evaluate_reports_errors_as_values() {
+    let mut state = State::new();
+    assert!(matches!(evaluate("nope + 1", &mut state), Err(EvalError::UndefinedVariable { .. })));
+    assert!(matches!(evaluate("1 +", &mut state), Err(EvalError::ParseError(_))));
+    // a failed evaluation leaves the state usable
+    assert_eq!(evaluate("2^10", &mut state), Ok(Value::Real(1024.0)));
+}
+
+#[test]
+fn registered_closures_are_callable() {
+    let mut state = State::new();
+    let offset = 100.0;
+    state.register("shift", 1, move |args| Ok(args[0] + offset));
+    state.register("safe_div", 2, |args| if args[1] == 0.0 { Err("divide by zero".to_string()) } else { Ok(args[0] / args[1]) });
+    assert_eq!(evaluate("shift(5) * 2", &mut state), Ok(Value::Real(210.0)));
+    assert_eq!(evaluate("safe_div(9, 3)", &mut state), Ok(Value::Real(3.0)));
+    assert!(evaluate("safe_div(9, 0)", &mut state).unwrap_err().to_string().contains("divide by zero"));
+    assert!(evaluate("shift(1, 2)", &mut state).is_err());
+}
+
+#[test]
+fn registered_closures_take_precedence_over_built_ins() {
+    let mut state = State::new();
+    state.register("sin", 1, |_| Ok(42.0));
+    assert_eq!(evaluate("sin(0)", &mut state), Ok(Value::Real(42.0)));
+}
+
+#[test]
+fn variables_can_be_set_before_evaluating() {
+    let mut state = State::new();
+    state.set_var("x", 3.0);
+    state.set_var("y", 0.5);
+    assert_eq!(evaluate("x*2+y", &mut state), Ok(Value::Real(6.5)));
+    evaluate("z = x + 1", &mut state).unwrap();
+    assert_eq!(state.get_var("z"), Some(Value::Real(4.0)));
+}
+
+#[test]
+fn compiled_expressions_match_the_interpreter() {
+    let compiled = function::CompiledExpr::new("x^2 + 1").unwrap();
+    let mut state = State::new();
+    for i in -20..=20 {
+        let x = i as f64 / 4.0;
+        let vars = std::collections::HashMap::from([("x".to_string(), x)]);
+        state.set_var("x", x);
+        assert_eq!(compiled.eval_with(&vars), evaluate("x^2 + 1", &mut state), "x = {}", x);
+    }
+    let missing = compiled.eval_with(&std::collections::HashMap::new());
+    assert!(matches!(missing, Err(EvalError::UndefinedVariable { .. })));
+}
+
+#[test]
+fn compiled_expressions_skip_the_per_call_setup() {
+    let compiled = function::CompiledExpr::new("x^2 + 3*x - 1").unwrap();
+    let mut vars = std::collections::HashMap::from([("x".to_string(), 0.0)]);
+    let time = |evaluate_at: &mut dyn FnMut(f64)| {
+        let start = std::time::Instant::now();
+        for i in 0..1000 {
+            evaluate_at(i as f64);
+        }
+        start.elapsed()
+    };
+    let compiled_time = time(&mut |x| {
+        *vars.get_mut("x").unwrap() = x;
+        assert_eq!(compiled.eval_with(&vars), Ok(Value::Real(x * x + 3.0 * x - 1.0)));
+    });
+    let mut state = State::new();
+    let interpreted_time = time(&mut |x| {
+        state.set_var("x", x);
+        evaluate("x^2 + 3*x - 1", &mut state).unwrap();
+    });
+    assert!(compiled_time * 10 < interpreted_time, "compiled {:?}, interpreted {:?}", compiled_time, interpreted_time);
+    // bindings from an earlier call don't leak into a later one
+    assert!(compiled.eval_with(&std::collections::HashMap::new()).is_err());
+}
+
+// A large tree evaluated many times: evaluation borrows the tree rather than
+// taking (and so first copying) it.
+#[test]
+fn evaluating_a_large_tree_repeatedly() {
+    // 400 terms, in groups so that the tree is wide rather than deep
+    let term = |i: usize| format!("(x * {} + sin(x) / {})", i, i);
+    let groups: Vec<String> = (0..20).map(|g| format!("({})", (1..=20).map(|i| term(20 * g + i)).collect::<Vec<_>>().join(" + "))).collect();
+    let source = groups.join(" + ");
+    let program = function::parse_program(&source).unwrap();
+    let mut state = State::new();
+    state.set_var("x", 0.5);
+    let first = function::eval_program(&program, &mut state).unwrap();
+    let start = std::time::Instant::now();
+    for _ in 0..500 {
+        assert_eq!(function::eval_program(&program, &mut state).unwrap(), first);
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed < std::time::Duration::from_secs(5), "took {:?}", elapsed);
+    assert_eq!(first, Some(evaluate(&source, &mut state).unwrap()));
+}
@@ -0,0 +1,250 @@
+use std::f64::consts::PI;
+
+use function::{EvalError, State, Value, evaluate};
+
+fn eval(input: &str) -> Result<Value, EvalError> {
+    evaluate(input, &mut State::new())
+}
+
+fn real(input: &str) -> f64 {
+    eval(input).and_then(|value| value.as_real()).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+fn assert_close(input: &str, expected: f64) {
+    let actual = real(input);
+    assert!((actual - expected).abs() <= 1e-9 * expected.abs().max(1.0), "{} = {}, expected {}", input, actual, expected);
+}
+
+fn assert_domain_error(input: &str) {
+    match eval(input) {
+        Err(EvalError::DomainError { .. }) => {}
+        other => panic!("{}: expected a domain error, got {:?}", input, other),
+    }
+}
+
+#[test]
+fn inverse_trig_at_the_domain_boundary() {
+    assert_close("asin(1)", PI / 2.0);
+    assert_close("asin(-1)", -PI / 2.0);
+    assert_close("acos(1)", 0.0);
+    assert_close("acos(-1)", PI);
+    assert_domain_error("asin(1.0001)");
+    assert_domain_error("acos(-1.0001)");
+}
+
+#[test]
+fn atan2_covers_all_quadrants_and_the_origin() {
+    assert_close("atan2(0, 0)", 0.0);
+    assert_close("atan2(1, 1)", PI / 4.0);
+    assert_close("atan2(1, -1)", 3.0 * PI / 4.0);
+    assert_close("atan2(-1, -1)", -3.0 * PI / 4.0);
+    assert!(matches!(eval("atan2(1)"), Err(EvalError::ArityMismatch { expected: 2, got: 1, .. })));
+}
+
+#[test]
+fn hyperbolic_functions_and_their_inverses() {
+    assert_eq!(real("tanh(0)"), 0.0);
+    assert_close("cosh(0)", 1.0);
+    assert_close("asinh(sinh(2))", 2.0);
+    assert_close("acosh(cosh(2))", 2.0);
+    assert_close("atanh(tanh(0.5))", 0.5);
+    assert_domain_error("acosh(0.5)");
+    assert_domain_error("atanh(1)");
+}
+
+#[test]
+fn logarithms_in_each_base() {
+    assert_close("ln(e)", 1.0);
+    assert_close("log10(1000)", 3.0);
+    assert_close("log2(1024)", 10.0);
+    assert_close("log(2, 8)", 3.0);
+    assert_domain_error("ln(0)");
+    assert_domain_error("log10(-1)");
+    assert_domain_error("log(1, 5)");
+}
+
+#[test]
+fn exp_sqrt_and_cbrt() {
+    assert_close("exp(0)", 1.0);
+    assert_close("exp(1)", std::f64::consts::E);
+    assert_close("sqrt(16)", 4.0);
+    assert_close("cbrt(-27)", -3.0);
+    // a negative square root has no real value; it's complex since complex
+    // numbers were added
+    assert!(eval("sqrt(-4)").unwrap().as_real().is_err());
+}
+
+#[test]
+fn rounding_goes_half_away_from_zero() {
+    assert_eq!(real("round(2.5)"), 3.0);
+    assert_eq!(real("round(-2.5)"), -3.0);
+    assert_eq!(real("floor(-0.1)"), -1.0);
+    assert_eq!(real("ceil(-0.1)"), 0.0);
+    assert_eq!(real("trunc(-2.7)"), -2.0);
+    assert_eq!(real("sign(-3)"), -1.0);
+    assert_eq!(real("sign(0)"), 0.0);
+}
+
+#[test]
+fn max_and_min_take_any_number_of_arguments() {
+    assert_eq!(real("max(7)"), 7.0);
+    assert_eq!(real("max(3, 9)"), 9.0);
+    assert_eq!(real("min(3, 9)"), 3.0);
+    assert_eq!(real("max(4, -2, 11, 5)"), 11.0);
+    assert_eq!(real("min(4, -2, 11, 5)"), -2.0);
+    assert!(eval("max()").is_err());
+}
+
+#[test]
+fn gcd_and_lcm_of_integers() {
+    assert_eq!(real("gcd(12, 18)"), 6.0);
+    assert_eq!(real("gcd(-12, 18)"), 6.0);
+    assert_eq!(real("lcm(4, 6)"), 12.0);
+    assert_eq!(real("lcm(0, 5)"), 0.0);
+    assert_domain_error("gcd(1.5, 3)");
+    assert_domain_error("lcm(4, 2.5)");
+}
+
+#[test]
+fn gcd_and_lcm_at_the_edge_of_the_integer_range() {
+    assert_eq!(real("gcd(-2^63, 0)"), 2f64.powi(63));
+    assert!(eval("lcm(2^62, 5)").is_err());
+    // 2^63 itself is one past i64::MAX
+    assert_domain_error("gcd(2^63, 1)");
+}
+
+#[test]
+fn factorial_and_gamma() {
+    assert_eq!(real("fact(5)"), 120.0);
+    assert_eq!(real("fact(0)"), 1.0);
+    assert_domain_error("fact(-1)");
+    assert_domain_error("fact(2.5)");
+    assert!(eval("fact(171)").is_err());
+    assert_close("gamma(5)", 24.0);
+    assert_close("gamma(0.5)", PI.sqrt());
+    assert_domain_error("gamma(-2)");
+}
+
+#[test]
+fn combinations_and_permutations() {
+    assert_eq!(real("ncr(5, 2)"), 10.0);
+    assert_eq!(real("npr(5, 2)"), 20.0);
+    assert_eq!(real("ncr(5, 0)"), 1.0);
+    // the intermediate factorials would overflow
+    assert_eq!(real("ncr(200, 2)"), 19900.0);
+    assert_domain_error("ncr(2, 5)");
+    assert_domain_error("npr(-1, 0)");
+}
+
+#[test]
+fn clamp_and_lerp() {
+    assert_eq!(real("clamp(5, 0, 3)"), 3.0);
+    assert_eq!(real("clamp(-5, 0, 3)"), 0.0);
+    assert_eq!(real("clamp(2, 0, 3)"), 2.0);
+    assert!(eval("clamp(1, 3, 0)").is_err());
+    assert_eq!(real("lerp(0, 10, 0.5)"), 5.0);
+    assert_eq!(real("lerp(2, 4, 0)"), 2.0);
+}
+
+#[test]
+fn hypot_does_not_overflow() {
+    assert_eq!(real("hypot(3, 4)"), 5.0);
+    // squaring either operand would overflow to inf
+    assert_close("hypot(1e200, 1e200)", 1e200 * 2f64.sqrt());
+}
+
+#[test]
+fn number_literals_take_an_exponent() {
+    assert_eq!(real("1e3"), 1000.0);
+    assert_eq!(real("2.5E-3"), 0.0025);
+    assert_eq!(real("1e+2"), 100.0);
+    // without digits after it, `e` is still the constant
+    assert_close("2e", 2.0 * std::f64::consts::E);
+}
+
+#[test]
+fn modulo_is_euclidean() {
+    assert_eq!(real("mod(7, 3)"), 1.0);
+    assert_eq!(real("mod(-7, 3)"), 2.0);
+    assert_eq!(real("-7 % 3"), 2.0);
+    assert!(matches!(eval("mod(5, 0)"), Err(EvalError::DivByZero)));
+    assert!(matches!(eval("5 % 0"), Err(EvalError::DivByZero)));
+}
+
+#[test]
+fn modulo_binds_like_multiplication() {
+    assert_eq!(real("1 + 7 % 3"), 2.0);
+    assert_eq!(real("2 * 7 % 4"), 2.0);
+    assert_eq!(real("7 % 4 * 2"), 6.0);
+}
+
+#[test]
+fn degree_radian_conversion() {
+    assert_close("rad2deg(pi)", 180.0);
+    assert_close("deg2rad(180)", PI);
+    assert_close("sin(deg2rad(90))", 1.0);
+}
+
+#[test]
+fn trig_follows_the_angle_mode() {
+    let mut state = State::new();
+    let sin_90 = |state: &mut State| evaluate("sin(90)", state).unwrap().as_real().unwrap();
+    assert!((sin_90(&mut state) - 90f64.sin()).abs() < 1e-12);
+    state.angle_mode = function::AngleMode::Degrees;
+    assert_eq!(sin_90(&mut state), 1.0);
+    assert_eq!(evaluate("asin(1)", &mut state).unwrap().as_real().unwrap(), 90.0);
+}
+
+#[test]
+fn bitwise_operations_on_integers() {
+    assert_eq!(real("band(12, 10)"), 8.0);
+    assert_eq!(real("bor(12, 10)"), 14.0);
+    assert_eq!(real("bxor(12, 10)"), 6.0);
+    assert_eq!(real("shl(1, 4)"), 16.0);
+    assert_eq!(real("shr(256, 4)"), 16.0);
+    assert_eq!(real("band(-1, 255)"), 255.0);
+    assert_domain_error("band(1.5, 1)");
+    assert!(eval("shl(1, 64)").is_err());
+    // beyond the 64-bit range the value can't be operated on exactly
+    assert_domain_error("band(1e19, 1)");
+}
+
+fn draws(seed: u64, input: &str) -> Vec<f64> {
+    let mut state = State::new();
+    state.seed(seed);
+    (0..5).map(|_| evaluate(input, &mut state).unwrap().as_real().unwrap()).collect()
+}
+
+#[test]
+fn seeded_rand_is_reproducible() {
+    let first = draws(42, "rand()");
+    assert_eq!(first, draws(42, "rand()"));
+    assert_ne!(first, draws(43, "rand()"));
+    assert!(first.iter().all(|x| (0.0..1.0).contains(x)));
+    assert!(draws(7, "rand(-2, 3)").iter().all(|x| (-2.0..3.0).contains(x)));
+    assert!(matches!(evaluate("rand(3, -2)", &mut State::new()), Err(EvalError::DomainError { .. })));
+}
+
+#[test]
+fn normal_distribution() {
+    assert_close("normpdf(0, 0, 1)", 0.398_942_280_401_432_7);
+    assert_close("normcdf(0, 0, 1)", 0.5);
+    assert!((real("normcdf(1.96, 0, 1)") - 0.975).abs() < 1e-4);
+    // mu and sigma default to the standard normal
+    assert_eq!(real("normpdf(0.5)"), real("normpdf(0.5, 0, 1)"));
+    assert_eq!(real("normcdf(-1)"), real("normcdf(-1, 0, 1)"));
+    assert_close("normpdf(4, 2, 2)", real("normpdf(1)") / 2.0);
+    assert_domain_error("normpdf(0, 0, 0)");
+    assert_domain_error("normcdf(0, 0, -1)");
+}
+
+#[test]
+fn error_function() {
+    assert!((real("erf(1)") - 0.842_700_792_949_715).abs() < 1e-7);
+    assert_eq!(real("erf(0)"), 0.0);
+    for x in [0.1, 0.5, 1.0, 2.5] {
+        assert_eq!(real(&format!("erf(-{})", x)), -real(&format!("erf({})", x)), "x = {}", x);
+        assert_close(&format!("erf({}) + erfc({})", x, x), 1.0);
+    }
+    assert!((real("erfc(2)") - 0.004_677_734_981_047_266).abs() < 1e-7);
+}
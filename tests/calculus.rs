@@ -0,0 +1,151 @@
+use function::{EvalError, State, Value, evaluate};
+
+fn run(state: &mut State, input: &str) -> Value {
+    evaluate(input, state).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+fn real(state: &mut State, input: &str) -> f64 {
+    run(state, input).as_real().unwrap()
+}
+
+fn assert_near(actual: f64, expected: f64, tolerance: f64) {
+    assert!((actual - expected).abs() <= tolerance, "got {}, expected {}", actual, expected);
+}
+
+#[test]
+fn numeric_derivative() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = x^2");
+    assert_near(real(&mut state, "diff(f, 3)"), 6.0, 1e-6);
+    assert_near(real(&mut state, "diff(sin, 0)"), 1.0, 1e-6);
+    assert!(matches!(evaluate("diff(g, 1)", &mut state), Err(EvalError::UnknownFunction { .. })));
+}
+
+#[test]
+fn symbolic_derivative_agrees_with_numeric() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = 3*x^3 - 2*x + sin(x) * cos(2*x) + exp(x/2)");
+    run(&mut state, "deriv(f)");
+    for x in [-2.0, -0.5, 0.0, 1.0, 2.5] {
+        let symbolic = real(&mut state, &format!("f'({})", x));
+        let numeric = real(&mut state, &format!("diff(f, {})", x));
+        assert_near(symbolic, numeric, 1e-5 * numeric.abs().max(1.0));
+    }
+}
+
+#[test]
+fn symbolic_derivative_in_a_chosen_variable() {
+    let mut state = State::new();
+    run(&mut state, "g(x, y) = x^2 * y");
+    run(&mut state, "deriv(g, y)");
+    assert_near(real(&mut state, "g'(3, 5)"), 9.0, 1e-12);
+    assert!(evaluate("deriv(g, z)", &mut state).is_err());
+}
+
+#[test]
+fn definite_integrals() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = x^2");
+    assert_near(real(&mut state, "integrate(f, 0, 3)"), 9.0, 1e-8);
+    assert_near(real(&mut state, "integrate(sin, 0, pi)"), 2.0, 1e-8);
+    // reversed bounds negate the result
+    assert_near(real(&mut state, "integrate(f, 3, 0)"), -9.0, 1e-8);
+    assert!(matches!(evaluate("integrate(nope, 0, 1)", &mut state), Err(EvalError::UnknownFunction { .. })));
+}
+
+#[test]
+fn newton_root_finding() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = x^2 - 2");
+    assert_near(real(&mut state, "solve(f, 1)"), 2f64.sqrt(), 1e-10);
+    assert_near(real(&mut state, "solve(f, -1)"), -(2f64.sqrt()), 1e-10);
+    // the derivative vanishes at the starting guess
+    run(&mut state, "g(x) = x^2 + 1");
+    assert!(evaluate("solve(g, 0)", &mut state).is_err());
+    // no real root, so the iteration never settles
+    assert!(evaluate("solve(g, 0.5)", &mut state).is_err());
+}
+
+#[test]
+fn taylor_polynomial_of_exp() {
+    let mut state = State::new();
+    run(&mut state, "taylor(exp, 0, 2)");
+    let p = |state: &mut State, x: f64| real(state, &format!("exp_taylor({})", x));
+    let (at_minus, at_zero, at_plus) = (p(&mut state, -1.0), p(&mut state, 0.0), p(&mut state, 1.0));
+    assert_near(at_zero, 1.0, 1e-12);
+    // for a quadratic, the second difference is twice the x^2 coefficient
+    assert_near((at_plus + at_minus - 2.0 * at_zero) / 2.0, 0.5, 1e-12);
+    assert_near((at_plus - at_minus) / 2.0, 1.0, 1e-12);
+}
+
+#[test]
+fn taylor_of_order_zero_is_the_constant_term() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = 3 + x^2");
+    run(&mut state, "taylor(f, 0, 0)");
+    assert_eq!(real(&mut state, "f_taylor(10)"), 3.0);
+    assert!(evaluate("taylor(nope, 0, 2)", &mut state).is_err());
+}
+
+#[test]
+fn summation_over_a_range() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = x");
+    assert_eq!(real(&mut state, "sum(f, 1, 100)"), 5050.0);
+    run(&mut state, "sq(k) = k^2");
+    assert_eq!(real(&mut state, "sum(sq, 1, 10)"), 385.0);
+    // an empty range sums to zero
+    assert_eq!(real(&mut state, "sum(f, 5, 4)"), 0.0);
+    assert!(evaluate("sum(nope, 1, 3)", &mut state).is_err());
+}
+
+#[test]
+fn product_over_a_range() {
+    let mut state = State::new();
+    run(&mut state, "g(x) = x");
+    assert_eq!(real(&mut state, "prod(g, 1, 5)"), 120.0);
+    assert_eq!(real(&mut state, "prod(g, 5, 4)"), 1.0);
+    assert!(evaluate("prod(g, 1.5, 3)", &mut state).is_err());
+    assert!(evaluate("prod(nope, 1, 3)", &mut state).is_err());
+}
+
+#[test]
+fn limits_across_a_removable_discontinuity() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = sin(x)/x; h(x) = x^2");
+    assert_near(real(&mut state, "limit(f, 0)"), 1.0, 1e-9);
+    assert_near(real(&mut state, "limit(h, 3)"), 9.0, 1e-9);
+}
+
+#[test]
+fn limits_that_do_not_exist() {
+    let mut state = State::new();
+    run(&mut state, "g(x) = 1/x; s(x) = sign(x)");
+    assert_eq!(evaluate("limit(g, 0)", &mut state), Err(EvalError::Runtime("limit does not exist".to_string())));
+    assert_eq!(evaluate("limit(s, 0)", &mut state), Err(EvalError::Runtime("limit does not exist".to_string())));
+}
+
+#[test]
+fn partial_derivatives_hold_the_other_variables_fixed() {
+    let mut state = State::new();
+    run(&mut state, "f(x, y) = x^2*y");
+    // at (3, 2): df/dx = 2xy = 12 and df/dy = x^2 = 9
+    assert_near(real(&mut state, "partial(f, x, 3, 2)"), 12.0, 1e-6);
+    assert_near(real(&mut state, "partial(f, y, 3, 2)"), 9.0, 1e-6);
+    assert_eq!(evaluate("partial(f, z, 3, 2)", &mut state).unwrap_err().to_string(), "'z' is not a parameter of 'f'");
+    assert!(matches!(evaluate("partial(f, x, 3)", &mut state), Err(EvalError::ArityMismatch { .. })));
+}
+
+#[test]
+fn gradient_lists_the_partials_in_parameter_order() {
+    let mut state = State::new();
+    run(&mut state, "f(x, y) = x^2 + y^2");
+    let Value::List(gradient) = run(&mut state, "gradient(f, 1, 2)") else {
+        panic!("gradient should be a list");
+    };
+    let gradient: Vec<f64> = gradient.iter().map(|g| g.as_real().unwrap()).collect();
+    assert_eq!(gradient.len(), 2);
+    assert_near(gradient[0], 2.0, 1e-6);
+    assert_near(gradient[1], 4.0, 1e-6);
+    assert!(matches!(evaluate("gradient(f, 1)", &mut state), Err(EvalError::ArityMismatch { .. })));
+}
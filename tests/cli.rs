@@ -0,0 +1,132 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+// Runs the binary with `input` piped to it, which puts it in quiet mode.
+fn pipe(args: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_function"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to start the interpreter");
+    child.stdin.take().unwrap().write_all(input.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn ans_holds_the_last_result() {
+    let output = pipe(&[], "ans\n2 + 3\nans * 10\n");
+    assert_eq!(stdout(&output), "5\n50\n");
+    assert!(stderr(&output).contains("Undefined variable: ans"), "{}", stderr(&output));
+}
+
+#[test]
+fn print_honors_the_precision() {
+    // print shows its value and, like any expression, the REPL then shows the result
+    let output = pipe(&[], ":precision 4\nprint(1/3)\n2/3\n:precision\n1/4\n");
+    assert_eq!(stdout(&output), "0.3333\n0.3333\n0.6667\n0.25\n");
+}
+
+#[test]
+fn parsing_prints_nothing_but_results() {
+    let output = pipe(&[], "1 + 2 * 3\nx = 2^3\n");
+    assert_eq!(stdout(&output), "7\n8\n");
+    assert_eq!(stderr(&output), "");
+}
+
+#[test]
+fn piped_input_prints_only_results() {
+    let output = pipe(&[], "x = 4\nf(y) = y + x\n\nf(1)\nbogus + 1\nx * 2\n");
+    assert_eq!(stdout(&output), "4\n0\n5\n8\n");
+    assert!(!stdout(&output).contains('>'), "a prompt was shown");
+    assert!(stderr(&output).contains("Undefined variable: bogus"), "{}", stderr(&output));
+}
+
+// A script in the temp directory, named after the test that writes it.
+fn script(name: &str, contents: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("function-cli-{}-{}.fl", std::process::id(), name));
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+fn run_files(paths: &[&std::path::Path]) -> Output {
+    let args: Vec<&str> = paths.iter().map(|path| path.to_str().unwrap()).collect();
+    pipe(&args, "")
+}
+
+#[test]
+fn files_run_in_order_against_one_state() {
+    let first = script("first", "a = 2\nb = a * 3\n");
+    let second = script("second", "a + b\n");
+    let output = run_files(&[&first, &second]);
+    assert_eq!(stdout(&output), "Line 1: a = 2 = 2\nLine 2: b = a * 3 = 6\nLine 1: a + b = 8\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn an_unreadable_file_fails_the_run() {
+    let missing = std::env::temp_dir().join("function-cli-no-such-file.fl");
+    let output = run_files(&[&missing]);
+    assert!(stdout(&output).contains("Could not read file"));
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn trace_shows_inner_steps_first() {
+    let output = pipe(&[], ":trace on\n2 + 3 * 4\n:trace off\n1 + 1\n");
+    assert_eq!(stdout(&output), "  3 * 4 = 12\n2 + 3 * 4 = 14\n14\n2\n");
+}
+
+#[test]
+fn base_conversions_print_with_a_prefix() {
+    // print shows its value and the REPL then shows the result
+    let output = pipe(&[], "print(hex(255))\nbin(5)\noct(8)\nhex(-255)\nhex(2.5)\n");
+    assert_eq!(stdout(&output), "0xff\n0xff\n0b101\n0o10\n-0xff\n");
+    assert!(stderr(&output).contains("hex expects integer arguments, got 2.5"), "{}", stderr(&output));
+}
+
+#[test]
+fn print_labels_values_with_strings() {
+    let output = pipe(&[], "x = 6 * 7\nprint(\"result:\", x)\n\"con\" + \"cat\"\n");
+    assert_eq!(stdout(&output), "42\nresult: 42\n42\nconcat\n");
+}
+
+#[test]
+fn print_puts_all_its_arguments_on_one_line() {
+    let output = pipe(&[], "print(1, 2 + 3, [4, 5])\n");
+    // the second line is the REPL showing print's result, its last argument
+    assert_eq!(stdout(&output), "1 5 [4, 5]\n[4, 5]\n");
+}
+
+#[test]
+fn a_failed_assertion_stops_the_run() {
+    let failing = script("failing", "x = 1\nassert(x == 2, \"x should be 2\")\nprint(\"not reached\")\n");
+    let later = script("later", "y = 3\n");
+    let output = run_files(&[&failing, &later]);
+    assert_eq!(stdout(&output), "Line 1: x = 1 = 1\nLine 2: Assertion failed: x should be 2\n");
+    assert_eq!(output.status.code(), Some(1));
+    let passing = script("passing", "assert(1)\n");
+    assert!(run_files(&[&passing]).status.success());
+}
+
+#[test]
+fn decimal_mode_warns_when_it_falls_back_to_floating_point() {
+    let output = pipe(&[], ":bigdecimal\n0.1 + 0.2\n10^-30\nsin(1)\n");
+    assert_eq!(stderr(&output), "Warning: 10 ^ -30 is computed in floating point\nWarning: sin is computed in floating point\n");
+    assert!(stdout(&output).starts_with("0.3\n0.000000000000000000000000000001\n"), "{}", stdout(&output));
+}
+
+#[test]
+fn endless_self_calls_are_reported_as_too_deep() {
+    let output = pipe(&[], "f(x) = f(x)\nf(1)\n");
+    assert!(stderr(&output).contains("maximum recursion depth exceeded"), "{}", stderr(&output));
+}
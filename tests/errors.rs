@@ -0,0 +1,96 @@
+use function::{EvalError, State, Value, evaluate};
+
+fn error(state: &mut State, input: &str) -> EvalError {
+    match evaluate(input, state) {
+        Ok(value) => panic!("{} evaluated to {:?}", input, value),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn typos_suggest_the_closest_name() {
+    let mut state = State::new();
+    let EvalError::UnknownFunction { name, suggestion, .. } = error(&mut state, "sinn(1)") else {
+        panic!("expected an unknown function");
+    };
+    assert_eq!((name.as_str(), suggestion.as_deref()), ("sinn", Some("sin")));
+    evaluate("radius = 2", &mut state).unwrap();
+    let EvalError::UndefinedVariable { suggestion, .. } = error(&mut state, "raduis * 2") else {
+        panic!("expected an undefined variable");
+    };
+    assert_eq!(suggestion.as_deref(), Some("radius"));
+    assert!(error(&mut state, "raduis").to_string().ends_with("(did you mean 'radius'?)"));
+    // nothing is suggested for a name unlike any known one
+    assert!(matches!(error(&mut state, "zzzzzz"), EvalError::UndefinedVariable { suggestion: None, .. }));
+}
+
+#[test]
+fn division_by_zero_is_an_error_by_default() {
+    let mut state = State::new();
+    assert_eq!(error(&mut state, "1/0"), EvalError::DivByZero);
+    assert_eq!(error(&mut state, "7 % 0"), EvalError::DivByZero);
+    assert_eq!(error(&mut state, "mod(5, 0)"), EvalError::DivByZero);
+    assert_eq!(evaluate("1/0.5", &mut state), Ok(Value::Real(2.0)));
+}
+
+#[test]
+fn ieee_division_gives_infinities() {
+    let mut state = State::new();
+    state.ieee_division = true;
+    assert_eq!(evaluate("1/0", &mut state), Ok(Value::Real(f64::INFINITY)));
+    assert_eq!(evaluate("-1/0", &mut state), Ok(Value::Real(f64::NEG_INFINITY)));
+    assert!(evaluate("0/0", &mut state).unwrap().as_real().unwrap().is_nan());
+    // a remainder has no IEEE infinity to fall back on
+    assert_eq!(error(&mut state, "7 % 0"), EvalError::DivByZero);
+}
+
+#[test]
+fn strict_mode_rejects_non_finite_results() {
+    let mut state = State::new();
+    state.strict_finite = true;
+    state.ieee_division = true;
+    let not_finite = EvalError::Runtime("result is not finite".to_string());
+    assert_eq!(error(&mut state, "0/0"), not_finite);
+    assert_eq!(error(&mut state, "inf - inf"), not_finite);
+    // an intermediate overflow fails even if later steps would make it finite
+    assert_eq!(error(&mut state, "1 / (1e308 * 10)"), not_finite);
+    assert_eq!(evaluate("2^10 - 1/4", &mut state), Ok(Value::Real(1023.75)));
+}
+
+#[test]
+fn failures_have_matchable_kinds() {
+    let mut state = State::new();
+    let arity = |name: &str, expected, got| EvalError::ArityMismatch { name: name.to_string(), expected, got };
+    assert_eq!(error(&mut state, "sin(1, 2)"), arity("sin", 1, 2));
+    assert_eq!(error(&mut state, "hypot(1)"), arity("hypot", 2, 1));
+    assert_eq!(error(&mut state, "gcd(1)"), arity("gcd", 2, 1));
+    evaluate("f(x) = x", &mut state).unwrap();
+    assert_eq!(error(&mut state, "f(1, 2)"), arity("f", 1, 2));
+    for (input, func) in [("asin(2)", "asin"), ("ln(-1)", "ln"), ("log(1, 8)", "log"), ("gamma(-1)", "gamma")] {
+        assert!(matches!(error(&mut state, input), EvalError::DomainError { func: f, .. } if f == func), "{}", input);
+    }
+    assert_eq!(error(&mut state, "mod(1, 0)"), EvalError::DivByZero);
+    assert!(matches!(error(&mut state, "nothing"), EvalError::UndefinedVariable { .. }));
+    assert!(matches!(error(&mut state, "nothing(1)"), EvalError::UnknownFunction { .. }));
+    // special forms that take a function name report an unknown one the same way
+    for input in ["partial(nope, x, 1)", "gradient(nope, 1)", "deriv(nope)", "taylor(nope, 0, 2)", "diff(nope, 1)", "integrate(nope, 0, 1)"] {
+        assert!(matches!(error(&mut state, input), EvalError::UnknownFunction { name, span: Some(_), .. } if name == "nope"), "{}", input);
+    }
+    assert!(matches!(error(&mut state, "1 +"), EvalError::ParseError(_)));
+    let as_std: Box<dyn std::error::Error> = Box::new(EvalError::DivByZero);
+    assert_eq!(as_std.to_string(), "division by zero");
+}
+
+#[test]
+fn errors_point_at_the_offending_name() {
+    let mut state = State::new();
+    let EvalError::UndefinedVariable { span: Some(span), .. } = error(&mut state, "1 + badvar * 2") else {
+        panic!("expected an undefined variable with a span");
+    };
+    assert_eq!((span.line, span.column, span.start, span.end), (1, 5, 4, 10));
+    assert!(error(&mut state, "1 + badvar * 2").to_string().contains("at line 1, column 5"));
+    let EvalError::UnknownFunction { span: Some(span), .. } = error(&mut state, "2 *\n  nofunc(1)") else {
+        panic!("expected an unknown function with a span");
+    };
+    assert_eq!((span.line, span.column), (2, 3));
+}
@@ -0,0 +1,182 @@
+use function::{EvalError, State, Value, evaluate};
+
+fn run(state: &mut State, input: &str) -> Value {
+    evaluate(input, state).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+fn real(state: &mut State, input: &str) -> f64 {
+    run(state, input).as_real().unwrap()
+}
+
+#[test]
+fn functions_with_several_parameters() {
+    let mut state = State::new();
+    run(&mut state, "area(w, h) = w * h");
+    assert_eq!(real(&mut state, "area(3, 4)"), 12.0);
+    run(&mut state, "avg3(a, b, c) = (a + b + c) / 3");
+    assert_eq!(real(&mut state, "avg3(1, 2, 6)"), 3.0);
+    assert_eq!(
+        evaluate("area(3)", &mut state),
+        Err(EvalError::ArityMismatch { name: "area".to_string(), expected: 2, got: 1 })
+    );
+}
+
+#[test]
+fn arity_is_checked_before_arguments_are_evaluated() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = x");
+    assert!(matches!(evaluate("f()", &mut state), Err(EvalError::ArityMismatch { expected: 1, got: 0, .. })));
+    // the division by zero would be reported first if the arguments ran
+    assert!(matches!(evaluate("f(1, 1/0)", &mut state), Err(EvalError::ArityMismatch { expected: 1, got: 2, .. })));
+}
+
+// Each call used to copy every global variable; with many globals defined a
+// recursive function slowed down in proportion.
+#[test]
+fn calls_do_not_copy_the_globals() {
+    let mut state = State::new();
+    state.memoize = false;
+    for i in 0..20_000 {
+        state.set_var(&format!("v{}", i), i as f64);
+    }
+    run(&mut state, "count(n) = n == 0 ? 0 : 1 + count(n - 1)");
+    let start = std::time::Instant::now();
+    for _ in 0..200 {
+        assert_eq!(real(&mut state, "count(40)"), 40.0);
+    }
+    let elapsed = start.elapsed();
+    assert!(elapsed < std::time::Duration::from_secs(2), "took {:?}", elapsed);
+}
+
+#[test]
+fn runaway_recursion_is_an_error_not_a_crash() {
+    // runs on the test thread's default stack, as an embedder's would
+    let mut state = State::new();
+    run(&mut state, "forever(n) = 1 + forever(n + 1)");
+    let err = evaluate("forever(0)", &mut state).unwrap_err();
+    assert!(err.to_string().contains("maximum recursion depth exceeded"), "{}", err);
+}
+
+#[test]
+fn a_function_that_only_calls_itself_fails_promptly() {
+    let mut state = State::new();
+    run(&mut state, "f(x) = f(x)");
+    let start = std::time::Instant::now();
+    let err = evaluate("f(1)", &mut state).unwrap_err();
+    assert_eq!(err.to_string(), "maximum recursion depth exceeded");
+    assert!(start.elapsed() < std::time::Duration::from_secs(2), "took {:?}", start.elapsed());
+}
+
+#[test]
+fn recursion_depth_limit_is_configurable() {
+    let mut state = State::new();
+    state.max_call_depth = 10;
+    run(&mut state, "depth(n) = n == 0 ? 0 : 1 + depth(n - 1)");
+    assert_eq!(real(&mut state, "depth(5)"), 5.0);
+    assert!(evaluate("depth(20)", &mut state).unwrap_err().to_string().contains("maximum recursion depth exceeded"));
+}
+
+#[test]
+fn lambdas_are_values() {
+    let mut state = State::new();
+    run(&mut state, "double = x -> 2 * x");
+    assert_eq!(real(&mut state, "double(21)"), 42.0);
+    run(&mut state, "add = (a, b) -> a + b");
+    assert_eq!(real(&mut state, "add(2, 3)"), 5.0);
+    assert!(matches!(evaluate("add(1)", &mut state), Err(EvalError::ArityMismatch { expected: 2, got: 1, .. })));
+}
+
+#[test]
+fn lambdas_capture_the_enclosing_parameters() {
+    let mut state = State::new();
+    run(&mut state, "adder(n) = x -> x + n");
+    run(&mut state, "add5 = adder(5)");
+    assert_eq!(real(&mut state, "add5(1)"), 6.0);
+}
+
+fn list(items: &[f64]) -> Value {
+    Value::List(items.iter().map(|x| Value::Real(*x)).collect())
+}
+
+#[test]
+fn map_and_filter() {
+    let mut state = State::new();
+    assert_eq!(run(&mut state, "map(sin, [0, pi/2])"), list(&[0.0, 1.0]));
+    assert_eq!(run(&mut state, "filter(x -> x > 2, [1,2,3,4])"), list(&[3.0, 4.0]));
+    run(&mut state, "sq(x) = x^2");
+    assert_eq!(run(&mut state, "map(sq, [1, 2, 3])"), list(&[1.0, 4.0, 9.0]));
+    assert_eq!(run(&mut state, "map(sq, [])"), list(&[]));
+    assert!(evaluate("map(sq, 3)", &mut state).is_err());
+}
+
+#[test]
+fn reduce_folds_from_the_initial_value() {
+    let mut state = State::new();
+    assert_eq!(real(&mut state, "reduce((a, b) -> a + b, 0, [1,2,3,4])"), 10.0);
+    assert_eq!(real(&mut state, "reduce((a, b) -> a * 10 + b, 0, [1,2,3])"), 123.0);
+    assert_eq!(real(&mut state, "reduce((a, b) -> a + b, 7, [])"), 7.0);
+    assert!(matches!(evaluate("reduce(x -> x, 0, [1])", &mut state), Err(EvalError::ArityMismatch { .. })));
+}
+
+#[test]
+fn piecewise_definitions() {
+    let mut state = State::new();
+    run(&mut state, "absolute(x) = { x < 0 : -x; x >= 0 : x }");
+    assert_eq!(real(&mut state, "absolute(-3)"), 3.0);
+    assert_eq!(real(&mut state, "absolute(4)"), 4.0);
+    run(&mut state, "positive(x) = { x > 0 : 1 }");
+    assert!(evaluate("positive(-1)", &mut state).unwrap_err().to_string().contains("no matching piece"));
+}
+
+#[test]
+fn functions_are_values_that_variables_can_hold() {
+    let mut state = State::new();
+    run(&mut state, "g = sin");
+    assert_eq!(real(&mut state, "g(0)"), 0.0);
+    run(&mut state, "sq(x) = x^2; h = sq");
+    assert_eq!(real(&mut state, "h(3)"), 9.0);
+    assert_eq!(run(&mut state, "map(h, [1, 2])"), Value::List(vec![Value::Real(1.0), Value::Real(4.0)]));
+    // a variable holding a number is not callable
+    run(&mut state, "n = 2");
+    assert!(evaluate("n(1)", &mut state).is_err());
+}
+
+fn time_fib(memoize: bool) -> std::time::Duration {
+    let mut state = State::new();
+    state.memoize = memoize;
+    run(&mut state, "fib(n) = n < 2 ? n : fib(n-1) + fib(n-2)");
+    let start = std::time::Instant::now();
+    assert_eq!(real(&mut state, "fib(22)"), 17711.0);
+    start.elapsed()
+}
+
+#[test]
+fn memoization_speeds_up_pure_recursion() {
+    let (uncached, cached) = (time_fib(false), time_fib(true));
+    // the cache turns tens of thousands of calls into a few dozen
+    assert!(cached * 10 < uncached, "cached {:?}, uncached {:?}", cached, uncached);
+}
+
+#[test]
+fn functions_reading_globals_are_not_cached() {
+    let mut state = State::new();
+    run(&mut state, "scale = 2; f(x) = x * scale");
+    assert_eq!(real(&mut state, "f(1)"), 2.0);
+    run(&mut state, "scale = 3");
+    assert_eq!(real(&mut state, "f(1)"), 3.0);
+    // a NaN argument is cached like any other
+    run(&mut state, "half(x) = x / 2");
+    assert!(real(&mut state, "half(nan) + half(nan)").is_nan());
+}
+
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    let mut state = State::new();
+    run(&mut state, "sum_to(n, acc) = n == 0 ? acc : sum_to(n - 1, acc + n)");
+    assert_eq!(real(&mut state, "sum_to(90000, 0)"), 4050045000.0);
+    run(&mut state, "count(n, acc) = if(n == 0, acc, count(n - 1, acc + 1))");
+    assert_eq!(real(&mut state, "count(90000, 0)"), 90000.0);
+    // the same depth without a tail call still hits the recursion limit
+    run(&mut state, "depth(n) = n == 0 ? 0 : 1 + depth(n - 1)");
+    assert!(evaluate("depth(100000)", &mut state).is_err());
+}
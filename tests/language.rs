@@ -0,0 +1,195 @@
+use function::{EvalError, State, Value, evaluate};
+
+fn eval(input: &str) -> Result<Value, EvalError> {
+    evaluate(input, &mut State::new())
+}
+
+fn real(input: &str) -> f64 {
+    eval(input).and_then(|value| value.as_real()).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+#[test]
+fn comparison_operators() {
+    let cases = [
+        ("1 < 2", 1.0),
+        ("2 < 1", 0.0),
+        ("2 > 1", 1.0),
+        ("1 > 1", 0.0),
+        ("1 <= 1", 1.0),
+        ("2 <= 1", 0.0),
+        ("1 >= 1", 1.0),
+        ("1 >= 2", 0.0),
+        ("3 == 3", 1.0),
+        ("3 == 4", 0.0),
+        ("3 != 4", 1.0),
+        ("3 != 3", 0.0),
+    ];
+    for (input, expected) in cases {
+        assert_eq!(real(input), expected, "{}", input);
+    }
+}
+
+#[test]
+fn equality_allows_for_rounding_error() {
+    assert_eq!(real("0.1 + 0.2 == 0.3"), 1.0);
+    assert_eq!(real("0.1 + 0.2 != 0.3"), 0.0);
+    // nearly equal values are neither less nor greater
+    assert_eq!(real("0.1 + 0.2 > 0.3"), 0.0);
+    assert_eq!(real("1 == 1.001"), 0.0);
+}
+
+#[test]
+fn if_evaluates_only_the_chosen_branch() {
+    let mut state = State::new();
+    assert_eq!(evaluate("if(1, 2, y = 5)", &mut state), Ok(Value::Real(2.0)));
+    assert!(state.get_var("y").is_none(), "the untaken branch assigned y");
+    assert_eq!(evaluate("if(0, y = 5, 3)", &mut state), Ok(Value::Real(3.0)));
+    assert!(state.get_var("y").is_none());
+}
+
+#[test]
+fn if_guards_a_division_by_zero() {
+    let mut state = State::new();
+    evaluate("safe_inv(x) = if(x == 0, 0, 1 / x)", &mut state).unwrap();
+    assert_eq!(evaluate("safe_inv(0)", &mut state), Ok(Value::Real(0.0)));
+    assert_eq!(evaluate("safe_inv(4)", &mut state), Ok(Value::Real(0.25)));
+}
+
+#[test]
+fn logical_operators_short_circuit() {
+    assert_eq!(real("and(0, 1/0)"), 0.0);
+    assert_eq!(real("or(2, 1/0)"), 1.0);
+    assert_eq!(real("and(1, -3)"), 1.0);
+    assert_eq!(real("or(0, 0)"), 0.0);
+    assert_eq!(real("not(0)"), 1.0);
+    assert_eq!(real("not(0.5)"), 0.0);
+    assert!(matches!(eval("and(1, 1/0)"), Err(EvalError::DivByZero)));
+}
+
+#[test]
+fn statements_separated_by_semicolons_share_state() {
+    let mut state = State::new();
+    assert_eq!(evaluate("a = 2; b = a * 3; a + b", &mut state), Ok(Value::Real(8.0)));
+    assert_eq!(state.get_var("b"), Some(Value::Real(6.0)));
+}
+
+#[test]
+fn comments_are_ignored() {
+    assert_eq!(real("1 + 2 # the rest of the line"), 3.0);
+    assert_eq!(real("1 /* inline */ + 2"), 3.0);
+    assert_eq!(real("1 + /* spans\nlines */ 2"), 3.0);
+    assert_eq!(eval("\"# not a comment\""), Ok(Value::Str("# not a comment".to_string())));
+}
+
+#[test]
+fn named_constants() {
+    assert_eq!(real("tau == 2*pi"), 1.0);
+    assert_eq!(real("phi^2 - phi"), 1.0);
+    assert_eq!(real("inf"), f64::INFINITY);
+    assert!(real("nan").is_nan());
+}
+
+#[test]
+fn assignment_shadows_a_constant() {
+    let mut state = State::new();
+    evaluate("e = 5", &mut state).unwrap();
+    assert_eq!(evaluate("e", &mut state), Ok(Value::Real(5.0)));
+    assert_eq!(evaluate("e", &mut State::new()), Ok(Value::Real(std::f64::consts::E)));
+}
+
+#[test]
+fn del_removes_variables_and_functions() {
+    let mut state = State::new();
+    evaluate("x = 1; f(y) = y + x", &mut state).unwrap();
+    evaluate("del x", &mut state).unwrap();
+    assert!(matches!(evaluate("x", &mut state), Err(EvalError::UndefinedVariable { .. })));
+    evaluate("del f", &mut state).unwrap();
+    assert!(matches!(evaluate("f(1)", &mut state), Err(EvalError::UnknownFunction { .. })));
+    assert!(evaluate("del x", &mut state).is_err());
+}
+
+#[test]
+fn ternary_evaluates_only_the_chosen_branch() {
+    assert_eq!(real("2 > 1 ? 10 : 20"), 10.0);
+    assert_eq!(real("2 < 1 ? 10 : 20"), 20.0);
+    assert_eq!(real("1 ? 2 : 1/0"), 2.0);
+    assert_eq!(real("0 ? 1/0 : 3"), 3.0);
+    // the condition binds looser than arithmetic, and branches nest to the right
+    assert_eq!(real("1 + 1 == 2 ? 0 ? 5 : 6 : 7"), 6.0);
+}
+
+#[test]
+fn while_loops_until_the_condition_fails() {
+    let mut state = State::new();
+    evaluate("i = 0; total = 0", &mut state).unwrap();
+    let last = evaluate("while(i < 10, i = i + 1, total = total + i)", &mut state).unwrap();
+    assert_eq!(last, Value::Real(55.0));
+    assert_eq!(state.get_var("total"), Some(Value::Real(55.0)));
+    // a loop that never runs has the value 0
+    assert_eq!(evaluate("while(0, 1/0)", &mut state), Ok(Value::Real(0.0)));
+}
+
+#[test]
+fn while_stops_at_the_iteration_cap() {
+    let mut state = State::new();
+    state.max_loop_iterations = 100;
+    evaluate("n = 0", &mut state).unwrap();
+    let err = evaluate("while(1, n = n + 1)", &mut state).unwrap_err();
+    assert_eq!(err.to_string(), "while exceeded 100 iterations");
+    assert_eq!(state.get_var("n"), Some(Value::Real(100.0)));
+}
+
+#[test]
+fn let_bindings_are_local() {
+    let mut state = State::new();
+    assert_eq!(evaluate("let x = 3 in x*x", &mut state), Ok(Value::Real(9.0)));
+    assert!(matches!(evaluate("x", &mut state), Err(EvalError::UndefinedVariable { .. })));
+    // an inner binding shadows a global and leaves it untouched
+    evaluate("y = 1", &mut state).unwrap();
+    assert_eq!(evaluate("let y = 5 in y + 1", &mut state), Ok(Value::Real(6.0)));
+    assert_eq!(state.get_var("y"), Some(Value::Real(1.0)));
+    assert_eq!(evaluate("let a = 2 in let b = a + 1 in a * b", &mut state), Ok(Value::Real(6.0)));
+}
+
+#[test]
+fn percent_after_a_number_is_a_hundredth() {
+    assert_eq!(real("50% == 0.5"), 1.0);
+    assert_eq!(real("200 * 10%"), 20.0);
+    // 10% is the number 0.1, not ten percent of 80
+    assert_eq!(real("80 + 10%"), 80.1);
+    // with an operand after it, % is the remainder
+    assert_eq!(real("7 % 2"), 1.0);
+    assert_eq!(real("7%2"), 1.0);
+}
+
+#[test]
+fn defined_checks_a_name_without_evaluating_it() {
+    let mut state = State::new();
+    evaluate("x = 1; f(a) = a", &mut state).unwrap();
+    for (input, expected) in [("defined(x)", 1.0), ("defined(f)", 1.0), ("defined(sin)", 1.0), ("defined(pi)", 1.0), ("defined(y)", 0.0)] {
+        assert_eq!(evaluate(input, &mut state), Ok(Value::Real(expected)), "{}", input);
+    }
+    assert!(evaluate("defined(1 + 1)", &mut state).is_err());
+}
+
+#[test]
+fn default_operator_falls_back_only_for_undefined_names() {
+    let mut state = State::new();
+    evaluate("y = 2", &mut state).unwrap();
+    assert_eq!(evaluate("x = y ?? 5", &mut state), Ok(Value::Real(2.0)));
+    assert_eq!(evaluate("x = z ?? 5", &mut state), Ok(Value::Real(5.0)));
+    assert_eq!(evaluate("y + w ?? 3", &mut state), Ok(Value::Real(3.0)));
+    // other errors, an unknown function among them, still propagate
+    assert!(matches!(evaluate("nope(1) ?? 3", &mut state), Err(EvalError::UnknownFunction { .. })));
+    assert_eq!(evaluate("(1/0) ?? 2", &mut state), Err(EvalError::DivByZero));
+}
+
+#[test]
+fn assert_passes_or_fails_with_its_message() {
+    let mut state = State::new();
+    assert_eq!(evaluate("assert(1 + 1 == 2)", &mut state), Ok(Value::Real(1.0)));
+    assert_eq!(evaluate("assert(0)", &mut state), Err(EvalError::AssertionFailed(None)));
+    let failed = evaluate("assert(2 < 1, \"two is not less than one\")", &mut state).unwrap_err();
+    assert_eq!(failed, EvalError::AssertionFailed(Some("two is not less than one".to_string())));
+    assert_eq!(failed.to_string(), "Assertion failed: two is not less than one");
+}
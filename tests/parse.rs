@@ -0,0 +1,142 @@
+use function::{EvalError, Expr, Span, State, Value, evaluate, parse};
+
+fn var(name: &str) -> Expr {
+    Expr::Variable(name.to_string(), Span::default())
+}
+
+fn num(n: f64) -> Expr {
+    Expr::Number(n)
+}
+
+fn bin(left: Expr, op: &str, right: Expr) -> Expr {
+    Expr::BinaryOp { left: Box::new(left), op: op.to_string(), right: Box::new(right) }
+}
+
+fn call(name: &str, args: Vec<Expr>) -> Expr {
+    Expr::FunctionCall { name: name.to_string(), args, span: Span::default() }
+}
+
+fn assert_parses_to(input: &str, expected: Expr) {
+    let actual = parse(input).unwrap_or_else(|e| panic!("{}: {}", input, e));
+    assert!(actual.same_structure(&expected), "{} parsed as {:?}", input, actual);
+}
+
+#[test]
+fn malformed_input_is_an_error_not_a_panic() {
+    for input in ["1 + * 2", "sin(", "(1 + 2", "1 2 )", "f(x) =", "[1, 2"] {
+        assert!(matches!(parse(input), Err(EvalError::ParseError(_))), "{} should not parse", input);
+    }
+}
+
+#[test]
+fn function_definition() {
+    let body = Box::new(bin(var("x"), "+", num(1.0)));
+    assert_parses_to("f(x) = x + 1", Expr::FunctionDef { name: "f".to_string(), params: vec!["x".to_string()], body });
+}
+
+#[test]
+fn multiplication_nests_under_addition() {
+    assert_parses_to("a + b * c", bin(var("a"), "+", bin(var("b"), "*", var("c"))));
+    assert_parses_to("a * b + c", bin(bin(var("a"), "*", var("b")), "+", var("c")));
+    assert_parses_to("a - b - c", bin(bin(var("a"), "-", var("b")), "-", var("c")));
+    assert_parses_to("(a + b) * c", bin(bin(var("a"), "+", var("b")), "*", var("c")));
+}
+
+#[test]
+fn parse_traces_are_recorded_only_when_asked_for() {
+    let (exprs, trace) = function::parse_program_debug("1 + 2 * 3", false).unwrap();
+    assert_eq!(exprs.len(), 1);
+    assert!(trace.is_empty());
+    let (_, trace) = function::parse_program_debug("1 + 2 * 3", true).unwrap();
+    assert!(trace.iter().any(|step| step.rule == "product" && step.text == "2 * 3"), "{:?}", trace);
+}
+
+#[test]
+fn juxtaposition_multiplies_except_after_a_name() {
+    assert_parses_to("f(2)", call("f", vec![num(2.0)]));
+    assert_parses_to("2(3)", bin(num(2.0), "*", num(3.0)));
+    assert_parses_to("2x", bin(num(2.0), "*", var("x")));
+    assert_parses_to("2(3+1)", bin(num(2.0), "*", bin(num(3.0), "+", num(1.0))));
+    let two_pi = evaluate("2pi", &mut State::new()).unwrap();
+    assert_eq!(two_pi, Value::Real(2.0 * std::f64::consts::PI));
+}
+
+fn fact(expr: Expr) -> Expr {
+    Expr::UnaryOp { op: "!".to_string(), expr: Box::new(expr) }
+}
+
+#[test]
+fn factorial_binds_tighter_than_power() {
+    assert_parses_to("3!^2", bin(fact(num(3.0)), "^", num(2.0)));
+    assert_parses_to("2^3!", bin(num(2.0), "^", fact(num(3.0))));
+    let mut state = State::new();
+    assert_eq!(evaluate("5! == 120", &mut state), Ok(Value::Real(1.0)));
+    assert_eq!(evaluate("3!^2", &mut state), Ok(Value::Real(36.0)));
+    assert_eq!(evaluate("2^3!", &mut state), Ok(Value::Real(64.0)));
+    assert!(matches!(evaluate("(-1)!", &mut state), Err(EvalError::DomainError { .. })));
+}
+
+#[test]
+fn power_is_right_associative() {
+    assert_parses_to("2^3^2", bin(num(2.0), "^", bin(num(3.0), "^", num(2.0))));
+    assert_eq!(evaluate("2^3^2", &mut State::new()), Ok(Value::Real(512.0)));
+}
+
+fn number(input: &str) -> Result<Value, EvalError> {
+    evaluate(input, &mut State::new())
+}
+
+#[test]
+fn hex_binary_and_octal_literals() {
+    assert_eq!(number("0xFF == 255"), Ok(Value::Real(1.0)));
+    assert_eq!(number("0b1010"), Ok(Value::Real(10.0)));
+    assert_eq!(number("0o17"), Ok(Value::Real(15.0)));
+    assert_eq!(number("0xff + 1"), Ok(Value::Real(256.0)));
+}
+
+#[test]
+fn underscores_separate_digits() {
+    assert_eq!(number("1_000 == 1000"), Ok(Value::Real(1.0)));
+    assert_eq!(number("1_000.5"), Ok(Value::Real(1000.5)));
+    assert_eq!(number("0xF_F"), Ok(Value::Real(255.0)));
+    for input in ["1__0", "1_", "1_.5"] {
+        assert!(matches!(number(input), Err(EvalError::ParseError(_))), "{} should not parse", input);
+    }
+}
+
+#[test]
+fn parse_errors_point_at_the_line_and_column() {
+    let Err(EvalError::ParseError(message)) = function::parse_program("1 +\n  2 )") else {
+        panic!("expected a parse error");
+    };
+    assert!(message.contains("line 2, column 5"), "{}", message);
+    assert!(message.contains("2 |   2 )"), "{}", message);
+    assert!(message.lines().last().unwrap().ends_with("|     ^"), "{}", message);
+}
+
+#[test]
+fn printed_source_parses_back_to_the_same_tree() {
+    let cases = [
+        ("a*(b+c)", "a * (b + c)"),
+        ("-x^2", "-x^2"),
+        ("a-(b-c)", "a - (b - c)"),
+        ("(a-b)-c", "a - b - c"),
+        ("(2^3)^2", "(2^3)^2"),
+        ("f(x+1, -y)", "f(x + 1, -y)"),
+    ];
+    for (input, printed) in cases {
+        let expr = parse(input).unwrap();
+        assert_eq!(function::expr_to_string(&expr), printed);
+        assert_parses_to(printed, expr);
+    }
+}
+
+#[test]
+fn spans_distinguish_positions_but_not_structure() {
+    let first = parse("x + 1").unwrap();
+    let second = parse("  x + 1").unwrap();
+    assert_ne!(first, second);
+    assert!(first.same_structure(&second));
+    assert!(!first.same_structure(&parse("x + 2").unwrap()));
+    assert_eq!(Span::default(), Span { start: 0, end: 0, line: 0, column: 0 });
+}
@@ -0,0 +1,54 @@
+use function::{Expr, State, evaluate, expr_to_string, fold_constants, parse, simplify};
+
+fn folded(input: &str) -> Expr {
+    fold_constants(parse(input).unwrap())
+}
+
+#[test]
+fn constant_subtrees_are_folded() {
+    assert!(folded("(2+3) * x").same_structure(&parse("5 * x").unwrap()));
+    assert!(folded("2^3^2").same_structure(&Expr::Number(512.0)));
+    assert!(folded("f(1+1)").same_structure(&parse("f(2)").unwrap()));
+    // nothing to fold when no operator has only numbers below it
+    assert!(folded("2 + 3 * x").same_structure(&parse("2 + 3 * x").unwrap()));
+    assert!(folded("sin(0) + x").same_structure(&parse("sin(0) + x").unwrap()));
+}
+
+#[test]
+fn division_by_zero_is_left_for_evaluation() {
+    let expr = folded("x * (1/0)");
+    assert_eq!(expr_to_string(&expr), "x * (1 / 0)");
+    let mut state = State::new();
+    state.set_var("x", 2.0);
+    assert!(function::eval_program(&[expr], &mut state).is_err());
+}
+
+#[test]
+fn folding_keeps_the_value() {
+    let mut state = State::new();
+    state.set_var("x", 1.5);
+    for input in ["(2+3) * x - 4^0.5", "-(4) + x / (10 - 8)", "3! * x"] {
+        let expected = evaluate(input, &mut state).unwrap();
+        assert_eq!(function::eval_program(&[folded(input)], &mut state), Ok(Some(expected)), "{}", input);
+    }
+}
+
+fn simplified(input: &str) -> String {
+    expr_to_string(&simplify(&parse(input).unwrap()))
+}
+
+#[test]
+fn simplify_applies_identities() {
+    assert_eq!(simplified("0*x + 1*y"), "y");
+    assert_eq!(simplified("1*cos(x) + 0"), "cos(x)");
+    assert_eq!(simplified("x^1 * 2 * 3"), "6 * x");
+    assert_eq!(simplified("x^0"), "1");
+}
+
+#[test]
+fn simplify_is_idempotent() {
+    for input in ["0*x + 1*y", "2 * (x^1 + 0) * 3", "(x + 0)^1 - 0 * sin(y)", "3 * x * 0 + z"] {
+        let once = simplify(&parse(input).unwrap());
+        assert!(simplify(&once).same_structure(&once), "{}", input);
+    }
+}
@@ -0,0 +1,241 @@
+use function::{EvalError, State, Value, evaluate};
+
+fn eval(input: &str) -> Result<Value, EvalError> {
+    evaluate(input, &mut State::new())
+}
+
+fn value(input: &str) -> Value {
+    eval(input).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+fn shown(input: &str) -> String {
+    let mut state = State::new();
+    let result = evaluate(input, &mut state).unwrap_or_else(|e| panic!("{}: {}", input, e));
+    state.format_value(&result)
+}
+
+#[test]
+fn complex_arithmetic() {
+    assert_eq!(value("sqrt(-4)"), Value::Complex(0.0, 2.0));
+    assert_eq!(shown("sqrt(-4)"), "2i");
+    // a product with no imaginary part is an ordinary real number
+    assert_eq!(value("(1+2i)*(1-2i)"), Value::Real(5.0));
+    assert_eq!(shown("(3+4i)/(1-2i)"), "-1 + 2i");
+    assert_eq!(value("abs(3+4i)"), Value::Real(5.0));
+}
+
+#[test]
+fn integer_powers_of_complex_numbers_are_exact() {
+    assert_eq!(value("i^2"), Value::Real(-1.0));
+    assert_eq!(value("(1+i)^3"), Value::Complex(-2.0, 2.0));
+    assert_eq!(value("(1+i)^-2"), Value::Complex(0.0, -0.5));
+}
+
+fn exact(input: &str) -> Value {
+    let mut state = State::new();
+    state.exact = true;
+    evaluate(input, &mut state).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+#[test]
+fn exact_mode_keeps_fractions() {
+    assert_eq!(exact("1/3 + 1/6"), Value::Rational(1, 2));
+    assert_eq!(exact("2/3 * 3/4 - 1/2"), Value::Rational(0, 1));
+    assert_eq!(exact("x = 1/3; x + x + x"), Value::Rational(1, 1));
+    assert_eq!(exact("sqrt(4/9)"), Value::Rational(2, 3));
+    // an irrational result has no exact form
+    assert!(matches!(exact("sqrt(2)"), Value::Real(x) if (x - 2f64.sqrt()).abs() < 1e-15));
+}
+
+fn list(items: &[f64]) -> Value {
+    Value::List(items.iter().map(|x| Value::Real(*x)).collect())
+}
+
+#[test]
+fn lists_combine_element_wise() {
+    assert_eq!(value("[1,2,3] + [10,20,30]"), list(&[11.0, 22.0, 33.0]));
+    assert_eq!(value("[1,2,3] * 2"), list(&[2.0, 4.0, 6.0]));
+    assert_eq!(value("10 - [1,2]"), list(&[9.0, 8.0]));
+    assert_eq!(value("sin([0, pi/2])"), list(&[0.0, 1.0]));
+    assert!(eval("[1,2] + [1]").is_err());
+    let mut state = State::new();
+    evaluate("v = [1, 2]", &mut state).unwrap();
+    assert_eq!(evaluate("v * v", &mut state), Ok(list(&[1.0, 4.0])));
+}
+
+#[test]
+fn list_reductions() {
+    assert_eq!(value("sum([1,2,3,4])"), Value::Real(10.0));
+    assert_eq!(value("mean([1,2,3,4])"), Value::Real(2.5));
+    assert_eq!(value("min([4,-1,7])"), Value::Real(-1.0));
+    assert_eq!(value("max([4,-1,7])"), Value::Real(7.0));
+    // sample standard deviation: the variance divides by n - 1
+    let stddev = value("stddev([2,4,4,4,5,5,7,9])").as_real().unwrap();
+    assert!((stddev - (32.0f64 / 7.0).sqrt()).abs() < 1e-12, "{}", stddev);
+    assert_eq!(value("sum([])"), Value::Real(0.0));
+    assert!(eval("mean([])").is_err());
+    assert!(eval("stddev([1])").is_err());
+}
+
+#[test]
+fn indexing_and_slicing() {
+    assert_eq!(value("[10,20,30][1]"), Value::Real(20.0));
+    assert_eq!(value("[10,20,30][-1]"), Value::Real(30.0));
+    assert!(eval("[10,20,30][3]").unwrap_err().to_string().contains("out of bounds"));
+    assert!(eval("[10,20,30][-4]").is_err());
+    assert!(eval("[10,20,30][0.5]").is_err());
+    assert_eq!(value("[10,20,30,40][1:3]"), list(&[20.0, 30.0]));
+    assert_eq!(value("[10,20,30,40][:2]"), list(&[10.0, 20.0]));
+    assert_eq!(value("[10,20,30,40][-1:]"), list(&[40.0]));
+    assert_eq!(value("[10,20,30,40][2:99]"), list(&[30.0, 40.0]));
+}
+
+#[test]
+fn ranges() {
+    assert_eq!(value("range(0, 5)"), list(&[0.0, 1.0, 2.0, 3.0, 4.0]));
+    assert_eq!(value("range(0, 1, 0.25)"), list(&[0.0, 0.25, 0.5, 0.75]));
+    assert_eq!(value("range(5, 0, -2)"), list(&[5.0, 3.0, 1.0]));
+    assert_eq!(value("range(3, 3)"), list(&[]));
+    assert!(eval("range(0, 5, 0)").is_err());
+}
+
+#[test]
+fn range_size_is_capped() {
+    assert!(eval("range(0, 10^12)").unwrap_err().to_string().contains("more than the limit"));
+    let mut state = State::new();
+    state.max_loop_iterations = 10;
+    assert!(evaluate("range(0, 11)", &mut state).is_err());
+    assert!(evaluate("range(0, 10)", &mut state).is_ok());
+}
+
+#[test]
+fn quantities_carry_units() {
+    assert_eq!(shown("3 m + 50 cm"), "3.5 m");
+    assert_eq!(shown("10 m / 2 s"), "5 m/s");
+    assert_eq!(shown("2 km * 3"), "6000 m");
+    assert_eq!(shown("(10 m)^2"), "100 m^2");
+    assert_eq!(value("1 km == 1000 m"), Value::Real(1.0));
+    assert_eq!(eval("3 m + 2 s").unwrap_err().to_string(), "Incompatible units: m and s");
+}
+
+#[test]
+fn compound_units_in_literals() {
+    assert_eq!(shown("9.8 m/s^2"), "9.8 m/s^2");
+    assert_eq!(shown("1 m/(s*A)"), "1 m/(s*A)");
+    assert_eq!(shown("5 s^-1"), "5 1/s");
+}
+
+#[test]
+fn unit_names_are_not_variables() {
+    assert!(matches!(eval("s + 1"), Err(EvalError::UndefinedVariable { .. })));
+    assert_eq!(value("m ?? 5"), Value::Real(5.0));
+    assert_eq!(value("defined(m)"), Value::Real(0.0));
+    // a variable of the same name takes the place of the unit
+    assert_eq!(value("m = 4; 2 m + m"), Value::Real(12.0));
+}
+
+fn decimal(input: &str) -> Value {
+    let mut state = State::new();
+    state.decimal = true;
+    evaluate(input, &mut state).unwrap_or_else(|e| panic!("{}: {}", input, e))
+}
+
+#[test]
+fn decimal_mode_adds_exactly() {
+    assert_eq!(decimal("0.1 + 0.2"), Value::decimal_parts(3, 1));
+    assert_eq!(decimal("0.1 + 0.2 == 0.3"), Value::Real(1.0));
+    assert_eq!(decimal("2.50 * 4 - 0.01"), Value::decimal_parts(999, 2));
+    let mut state = State::new();
+    state.decimal = true;
+    assert_eq!(state.format_value(&decimal("1.10 + 2.20")), "3.3");
+}
+
+#[test]
+fn decimal_mode_falls_back_to_floating_point() {
+    assert_eq!(decimal("sin(1)"), Value::Real(1f64.sin()));
+    assert_eq!(decimal("sqrt(2) * 1.5"), Value::Real(2f64.sqrt() * 1.5));
+    // a quotient with no exact decimal form isn't cut off
+    assert_eq!(decimal("10^-30"), Value::Real(1e-30));
+    assert_eq!(decimal("1/3"), Value::Real(1.0 / 3.0));
+    assert_eq!(decimal("2^200"), Value::Real(2f64.powi(200)));
+    assert_eq!(decimal("1/4"), Value::decimal_parts(25, 2));
+}
+
+fn matrix(rows: &[&[f64]]) -> Value {
+    Value::Matrix(rows.iter().map(|row| row.to_vec()).collect())
+}
+
+#[test]
+fn matrix_multiplication_and_determinant() {
+    assert_eq!(value("[[1,2],[3,4]] * [[5,6],[7,8]]"), matrix(&[&[19.0, 22.0], &[43.0, 50.0]]));
+    assert_eq!(value("[[1,2],[3,4]]^2"), matrix(&[&[7.0, 10.0], &[15.0, 22.0]]));
+    assert_eq!(value("transpose([[1,2],[3,4]])"), matrix(&[&[1.0, 3.0], &[2.0, 4.0]]));
+    assert!((value("det([[1,2],[3,4]])").as_real().unwrap() + 2.0).abs() < 1e-12);
+    assert_eq!(value("det([[1,2],[3,4]]) == -2"), Value::Real(1.0));
+    let Value::Matrix(inverse) = value("inv([[1,2],[3,4]]) * [[1,2],[3,4]]") else {
+        panic!("expected a matrix");
+    };
+    assert!(inverse.iter().flatten().zip([1.0, 0.0, 0.0, 1.0]).all(|(x, y)| (x - y).abs() < 1e-12));
+    assert_eq!(eval("[[1,2],[3,4]] * [[1,2,3]]").unwrap_err().to_string(), "Matrix dimension mismatch: 2x2 * 1x3");
+}
+
+#[test]
+fn matrices_act_as_lists_of_rows() {
+    let mut state = State::new();
+    evaluate("A = [[1,2],[3,4]]", &mut state).unwrap();
+    let mut run = |input: &str| evaluate(input, &mut state).unwrap_or_else(|e| panic!("{}: {}", input, e));
+    assert_eq!(run("A[1]"), list(&[3.0, 4.0]));
+    assert_eq!(run("sum(A)"), list(&[4.0, 6.0]));
+    assert_eq!(run("mean(A)"), list(&[2.0, 3.0]));
+    assert_eq!(run("map(r -> sum(r), A)"), list(&[3.0, 7.0]));
+    assert_eq!(run("reduce((a, r) -> a + r, [0, 0], A)"), list(&[4.0, 6.0]));
+    assert_eq!(run("filter(r -> r[0] > 1, A)"), matrix(&[&[3.0, 4.0]]));
+    assert_eq!(run("A == [[1,2],[3,4]]"), matrix(&[&[1.0, 1.0], &[1.0, 1.0]]));
+    assert_eq!(run("A + [1,2]"), matrix(&[&[2.0, 3.0], &[5.0, 6.0]]));
+}
+
+#[test]
+fn huge_matrix_powers_are_quick_or_refused() {
+    assert_eq!(value("[[1]]^(10^15)"), matrix(&[&[1.0]]));
+    assert!(eval("[[1]]^(10^300)").unwrap_err().to_string().contains("Matrix power is too large"));
+}
+
+#[test]
+fn dot_and_cross_products() {
+    assert_eq!(value("dot([1,2,3],[4,5,6]) == 32"), Value::Real(1.0));
+    assert_eq!(value("cross([1,0,0],[0,1,0])"), list(&[0.0, 0.0, 1.0]));
+    assert_eq!(value("cross([0,1,0],[1,0,0])"), list(&[0.0, 0.0, -1.0]));
+    assert_eq!(eval("dot([1],[1,2])").unwrap_err().to_string(), "dot needs lists of equal length, got 1 and 2");
+    assert_eq!(eval("cross([1,2],[3,4])").unwrap_err().to_string(), "cross needs lists of length 3, got length 2");
+}
+
+#[test]
+fn vector_norms() {
+    assert_eq!(value("norm([3,4])"), Value::Real(5.0));
+    assert_eq!(value("norm([3,-4], 1)"), Value::Real(7.0));
+    assert_eq!(value("norm([3,-4], inf)"), Value::Real(4.0));
+    // the 2-norm is scaled so that squaring large entries doesn't overflow
+    let big = value("norm([1e200, 1e200])").as_real().unwrap();
+    assert!((big / 1e200 - 2f64.sqrt()).abs() < 1e-12, "{}", big);
+    assert!(eval("norm([])").is_err());
+    assert!(eval("norm([1,2], 0)").is_err());
+}
+
+#[test]
+fn format_fills_placeholders() {
+    assert_eq!(value("format(\"{:.2} and {}\", pi, 3)"), Value::Str("3.14 and 3".to_string()));
+    assert_eq!(value("format(\"{{}} {}\", 5)"), Value::Str("{} 5".to_string()));
+    assert_eq!(eval("format(\"{} {}\", 1)").unwrap_err().to_string(), "format: template has 2 placeholder(s) but got 1 argument(s)");
+    assert!(eval("format(\"{}\", 1, 2)").is_err());
+    assert!(matches!(eval("format(\"{:.1000}\", 1)"), Ok(Value::Str(s)) if s.len() == 1002));
+    assert_eq!(eval("format(\"{:.1001}\", 1)").unwrap_err().to_string(), "format: precision 1001 is more than the limit of 1000");
+}
+
+#[test]
+fn unit_exponents_that_overflow_are_errors() {
+    let out_of_range = EvalError::Runtime("unit exponent out of range".to_string());
+    assert_eq!(eval("1 m^100 * 1 m^100"), Err(out_of_range.clone()));
+    assert_eq!(eval("1 m^-128 / 1 m^1"), Err(out_of_range));
+    assert_eq!(shown("1 m^100 * 1 m^27"), "1 m^127");
+    assert!(eval("(1 m^100)^2").is_err());
+}